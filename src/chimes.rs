@@ -1,44 +1,558 @@
+use crate::tones;
+use eyre::Context;
 use eyre::Result;
+use rodio::buffer::SamplesBuffer;
+use rodio::source::UniformSourceIterator;
 use rodio::Decoder;
 use rodio::OutputStream;
+use rodio::OutputStreamHandle;
 use rodio::Sink;
-use std::io::BufReader;
+use rodio::Source;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
 use std::io::Cursor;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
 
 // Embed the MP3 files directly into the binary.
 const ACTIVATE_MP3: &[u8] = include_bytes!("assets/activate.mp3");
 const DEACTIVATE_MP3: &[u8] = include_bytes!("assets/deactivate.mp3");
 
-/// Plays the provided audio data.
-/// This function creates an output stream, decodes the audio from memory,
-/// and plays it synchronously (waiting until playback completes).
-pub fn play_sound_from_bytes(audio_data: &'static [u8]) -> Result<()> {
-    // Create an output stream.
-    let (_stream, stream_handle) = OutputStream::try_default()?;
-    let sink = Sink::try_new(&stream_handle)?;
+// Whether chimes are played at all, from the `[sounds]` config section.
+static ENABLED: AtomicBool = AtomicBool::new(true);
 
-    // Create a Cursor so we can use the audio data as a stream.
-    let cursor = Cursor::new(audio_data);
-    let reader = BufReader::new(cursor);
+/// Turns all chimes on or off.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+/// The sounds the controller can ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chime {
+    /// Locked to the monitor with this number in `cursor-lock list`.
+    Activate(usize),
+    /// Released the lock on the monitor with this number.
+    Deactivate(usize),
+    /// Announces the monitor the lock moved to, by its number in `cursor-lock list`.
+    Monitor(usize),
+    /// The leader key was pressed and waits for a digit.
+    Armed,
+    /// The lock was reapplied after the foreground window changed.
+    Reapplied,
+    /// The cursor ran into the edge of the monitor it is locked to.
+    EdgeHit,
+    /// A command failed.
+    Error,
+}
+
+/// The events a sound pack can give a sound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundEvent {
+    Lock,
+    Unlock,
+    /// Played once per monitor number, like the default beeps.
+    Monitor,
+    Reapplied,
+    Edge,
+    Error,
+}
+
+impl SoundEvent {
+    pub const ALL: [SoundEvent; 6] = [
+        SoundEvent::Lock,
+        SoundEvent::Unlock,
+        SoundEvent::Monitor,
+        SoundEvent::Reapplied,
+        SoundEvent::Edge,
+        SoundEvent::Error,
+    ];
+
+    /// The file name, without extension, a pack directory holds this event's sound under.
+    pub fn name(self) -> &'static str {
+        match self {
+            SoundEvent::Lock => "lock",
+            SoundEvent::Unlock => "unlock",
+            SoundEvent::Monitor => "monitor",
+            SoundEvent::Reapplied => "reapplied",
+            SoundEvent::Edge => "edge",
+            SoundEvent::Error => "error",
+        }
+    }
+}
+
+/// What lock and unlock sound like when no file is given for them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SoundStyle {
+    /// The chimes built into the binary.
+    #[default]
+    Chimes,
+    /// Beeps that rise when locking and fall when unlocking, one per monitor number.
+    Tones,
+}
+
+/// The formats sound packs may use, by file extension.
+const EXTENSIONS: [&str; 4] = ["wav", "ogg", "flac", "mp3"];
+
+/// A decoded sound for each event that has one. Events without one play the default chime, or
+/// nothing if there is none.
+pub struct SoundPack {
+    sounds: HashMap<SoundEvent, SamplesBuffer<i16>>,
+    /// Scales every chime.
+    volume: f32,
+    /// Scales one event's chime on top of `volume`.
+    volumes: HashMap<SoundEvent, f32>,
+}
+
+impl SoundPack {
+    /// The chimes built into the binary.
+    pub fn embedded() -> Result<Self> {
+        Ok(Self {
+            sounds: HashMap::from([
+                (SoundEvent::Lock, decode(Cursor::new(ACTIVATE_MP3))?),
+                (SoundEvent::Unlock, decode(Cursor::new(DEACTIVATE_MP3))?),
+            ]),
+            volume: 1.0,
+            volumes: HashMap::new(),
+        })
+    }
+
+    /// No sounds at all, so lock and unlock fall back to the synthesized tones.
+    pub fn synthesized() -> Self {
+        Self {
+            sounds: HashMap::new(),
+            volume: 1.0,
+            volumes: HashMap::new(),
+        }
+    }
+
+    /// Sets the volume of every chime, and of some events on top of that. 1.0 plays sounds as recorded.
+    pub fn with_volumes(mut self, volume: f32, volumes: &[(SoundEvent, f32)]) -> Self {
+        self.volume = volume;
+        self.volumes.extend(volumes.iter().copied());
+        self
+    }
 
-    // Decode the audio.
-    let source = Decoder::new(reader)?;
+    /// The sounds of `style`, overridden by `dir/<event>.<ext>` for each event found in `dir`, and
+    /// then by `files`. Every file is decoded now so a broken one stops startup instead of a chime.
+    pub fn load(
+        style: SoundStyle,
+        dir: Option<&Path>,
+        files: &[(SoundEvent, PathBuf)],
+    ) -> Result<Self> {
+        let mut pack = match style {
+            SoundStyle::Chimes => Self::embedded()?,
+            SoundStyle::Tones => Self::synthesized(),
+        };
+        if let Some(dir) = dir {
+            if !dir.is_dir() {
+                eyre::bail!("The sound pack {} is not a directory", dir.display());
+            }
+            for event in SoundEvent::ALL {
+                let found = EXTENSIONS
+                    .iter()
+                    .map(|extension| dir.join(format!("{}.{}", event.name(), extension)))
+                    .find(|path| path.is_file());
+                if let Some(path) = found {
+                    pack.sounds.insert(event, decode_file(&path)?);
+                }
+            }
+        }
+        for (event, path) in files {
+            pack.sounds.insert(*event, decode_file(path)?);
+        }
+        Ok(pack)
+    }
 
-    // Append the source to the sink.
-    sink.append(source);
+    fn source(&self, chime: Chime) -> Option<Box<dyn Source<Item = f32> + Send>> {
+        let sound = |event| self.sounds.get(&event).cloned();
+        let (event, source): (_, Box<dyn Source<Item = f32> + Send>) = match chime {
+            Chime::Activate(monitor) => (
+                Some(SoundEvent::Lock),
+                match sound(SoundEvent::Lock) {
+                    Some(sound) => Box::new(sound.convert_samples()),
+                    None => Box::new(tones::locked(monitor)),
+                },
+            ),
+            Chime::Deactivate(monitor) => (
+                Some(SoundEvent::Unlock),
+                match sound(SoundEvent::Unlock) {
+                    Some(sound) => Box::new(sound.convert_samples()),
+                    None => Box::new(tones::released(monitor)),
+                },
+            ),
+            Chime::Monitor(count) => (
+                Some(SoundEvent::Monitor),
+                match sound(SoundEvent::Monitor) {
+                    Some(sound) => Box::new(
+                        rodio::source::from_iter(std::iter::repeat_n(sound, count))
+                            .convert_samples(),
+                    ),
+                    None => Box::new(tones::beeps(count)),
+                },
+            ),
+            Chime::Armed => (None, Box::new(tones::armed())),
+            Chime::Reapplied => (
+                Some(SoundEvent::Reapplied),
+                Box::new(sound(SoundEvent::Reapplied)?.convert_samples()),
+            ),
+            Chime::EdgeHit => (
+                Some(SoundEvent::Edge),
+                Box::new(sound(SoundEvent::Edge)?.convert_samples()),
+            ),
+            Chime::Error => (
+                Some(SoundEvent::Error),
+                Box::new(sound(SoundEvent::Error)?.convert_samples()),
+            ),
+        };
+        let volume = event
+            .and_then(|event| self.volumes.get(&event))
+            .unwrap_or(&1.0);
+        Some(Box::new(source.amplify(self.volume * volume)))
+    }
+}
+
+/// Plays chimes. The controller goes through this rather than the audio device so tests can fake it.
+/// Playing never fails and never waits for the sound; without an audio device chimes are just silent.
+pub trait ChimePlayer: Send {
+    fn play(&mut self, chime: Chime);
+}
+
+/// Where chimes end up once the pack has turned them into audio.
+pub trait ChimeOutput {
+    /// Starts playing `source`, cutting off the previous chime if the output plays in real time.
+    fn play(&mut self, source: Box<dyn Source<Item = f32> + Send>) -> Result<()>;
+}
+
+/// Plays chimes on a dedicated audio thread that keeps one output stream open, so callers never
+/// wait for a chime and a new one cuts off the one still playing.
+pub struct RodioChimes {
+    sender: Sender<Chime>,
+}
+
+impl RodioChimes {
+    pub fn spawn(pack: SoundPack) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || run_audio(pack, receiver));
+        Self { sender }
+    }
+}
+
+impl ChimePlayer for RodioChimes {
+    fn play(&mut self, chime: Chime) {
+        if ENABLED.load(Ordering::SeqCst) {
+            // The audio thread only stops if the process is going down anyway.
+            let _ = self.sender.send(chime);
+        }
+    }
+}
+
+/// Renders chimes into an output on the calling thread, e.g. a WAV file on machines without audio.
+pub struct RenderedChimes<O> {
+    pack: SoundPack,
+    output: O,
+    warned: bool,
+}
+
+impl<O: ChimeOutput> RenderedChimes<O> {
+    pub fn new(pack: SoundPack, output: O) -> Self {
+        Self {
+            pack,
+            output,
+            warned: false,
+        }
+    }
 
-    // Wait until the sound finishes playing.
-    sink.sleep_until_end();
+    fn render(&mut self, chime: Chime) {
+        let Some(source) = self.pack.source(chime) else {
+            return;
+        };
+        if let Err(e) = self.output.play(source) {
+            if !self.warned {
+                eprintln!("Failed to play a chime: {:#}", e);
+                self.warned = true;
+            }
+        }
+    }
+}
 
-    Ok(())
+impl<O: ChimeOutput + Send> ChimePlayer for RenderedChimes<O> {
+    fn play(&mut self, chime: Chime) {
+        if ENABLED.load(Ordering::SeqCst) {
+            self.render(chime);
+        }
+    }
 }
 
-/// Plays the activation chime.
-pub fn play_activation() -> Result<()> {
-    play_sound_from_bytes(ACTIVATE_MP3)
+/// The default audio device. The stream isn't `Send`, so this lives on the audio thread.
+struct DeviceOutput {
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    /// Dropping the sink stops whatever it is still playing.
+    _playing: Option<Sink>,
 }
 
-/// Plays the deactivation chime.
-pub fn play_deactivation() -> Result<()> {
-    play_sound_from_bytes(DEACTIVATE_MP3)
+impl ChimeOutput for DeviceOutput {
+    fn play(&mut self, source: Box<dyn Source<Item = f32> + Send>) -> Result<()> {
+        let sink = Sink::try_new(&self.handle)?;
+        sink.append(source);
+        self._playing = Some(sink);
+        Ok(())
+    }
+}
+
+/// Appends every chime in full to a mono 16-bit WAV, keeping the header valid after each one.
+pub struct WavOutput<W> {
+    writer: W,
+    samples: u32,
+}
+
+impl WavOutput<File> {
+    pub fn create(path: &Path) -> Result<Self> {
+        let create = || -> Result<_> { Self::new(File::create(path)?) };
+        create().wrap_err_with(|| format!("Failed to create {}", path.display()))
+    }
+}
+
+impl<W: Write + Seek> WavOutput<W> {
+    pub fn new(writer: W) -> Result<Self> {
+        let mut output = Self { writer, samples: 0 };
+        output.write_header()?;
+        Ok(output)
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()> {
+        let data = self.samples * 2;
+        let mut header = Vec::with_capacity(44);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(36 + data).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        header.extend_from_slice(&1u16.to_le_bytes()); // mono
+        header.extend_from_slice(&tones::SAMPLE_RATE.to_le_bytes());
+        header.extend_from_slice(&(tones::SAMPLE_RATE * 2).to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&16u16.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&data.to_le_bytes());
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&header)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> ChimeOutput for WavOutput<W> {
+    fn play(&mut self, source: Box<dyn Source<Item = f32> + Send>) -> Result<()> {
+        let samples: Vec<i16> = UniformSourceIterator::new(source, 1, tones::SAMPLE_RATE).collect();
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect();
+        self.writer.write_all(&bytes)?;
+        self.samples += samples.len() as u32;
+        self.write_header()?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Keeps each chime as its own clip of mono samples, so tests can listen to what was played.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MemoryOutput(std::sync::Arc<std::sync::Mutex<Vec<Vec<f32>>>>);
+
+#[cfg(test)]
+impl MemoryOutput {
+    /// The clips played since the last call, oldest first.
+    pub fn take(&self) -> Vec<Vec<f32>> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+#[cfg(test)]
+impl ChimeOutput for MemoryOutput {
+    fn play(&mut self, source: Box<dyn Source<Item = f32> + Send>) -> Result<()> {
+        let clip = UniformSourceIterator::new(source, 1, tones::SAMPLE_RATE).collect();
+        self.0.lock().unwrap().push(clip);
+        Ok(())
+    }
+}
+
+fn decode<R>(data: R) -> Result<SamplesBuffer<i16>>
+where
+    R: std::io::Read + std::io::Seek + Send + Sync + 'static,
+{
+    let decoder = Decoder::new(data)?;
+    let channels = decoder.channels();
+    let sample_rate = decoder.sample_rate();
+    Ok(SamplesBuffer::new(
+        channels,
+        sample_rate,
+        decoder.collect::<Vec<i16>>(),
+    ))
+}
+
+fn decode_file(path: &Path) -> Result<SamplesBuffer<i16>> {
+    let read = || -> Result<_> { decode(Cursor::new(std::fs::read(path)?)) };
+    read().wrap_err_with(|| format!("Failed to load the sound {}", path.display()))
+}
+
+/// Owns the output stream and plays each chime as it arrives, stopping the previous one.
+fn run_audio(pack: SoundPack, chimes: Receiver<Chime>) {
+    let (_stream, handle) = match OutputStream::try_default() {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("No audio output ({}); chimes are off.", e);
+            // Keep receiving so senders never notice.
+            for _ in chimes {}
+            return;
+        }
+    };
+    let device = DeviceOutput {
+        _stream,
+        handle,
+        _playing: None,
+    };
+    let mut rendered = RenderedChimes::new(pack, device);
+    for chime in chimes {
+        rendered.render(chime);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn embedded_chimes_decode() {
+        let pack = SoundPack::embedded().unwrap();
+        for event in [SoundEvent::Lock, SoundEvent::Unlock] {
+            let length = pack.sounds[&event].total_duration().unwrap();
+            assert!(length > Duration::ZERO && length < Duration::from_secs(5));
+        }
+    }
+
+    /// A mono 16-bit WAV of `samples` silent samples at 8 kHz.
+    fn wav(samples: usize) -> Vec<u8> {
+        let data = (samples * 2) as u32;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+        wav.extend_from_slice(&8000u32.to_le_bytes());
+        wav.extend_from_slice(&16000u32.to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data.to_le_bytes());
+        wav.resize(44 + samples * 2, 0);
+        wav
+    }
+
+    #[test]
+    fn packs_override_the_defaults_and_reject_broken_files() {
+        let dir = std::env::temp_dir().join(format!("cursor-lock-pack-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("lock.wav"), wav(800)).unwrap();
+        std::fs::write(dir.join("monitor.wav"), wav(80)).unwrap();
+        std::fs::write(dir.join("readme.txt"), "ignored").unwrap();
+
+        let pack = SoundPack::load(SoundStyle::Chimes, Some(&dir), &[]).unwrap();
+        let samples = |chime| pack.source(chime).map(|source| source.count());
+        assert_eq!(samples(Chime::Activate(1)), Some(800));
+        // The monitor sound plays once per monitor number.
+        assert_eq!(samples(Chime::Monitor(3)), Some(240));
+        // Unlock keeps the embedded chime; edge hits have no default.
+        assert!(samples(Chime::Deactivate(1)).unwrap() > 0);
+        assert_eq!(samples(Chime::EdgeHit), None);
+
+        std::fs::write(dir.join("broken.wav"), "not a sound").unwrap();
+        let files = [(SoundEvent::Edge, dir.join("broken.wav"))];
+        let error = SoundPack::load(SoundStyle::Chimes, Some(&dir), &files)
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", error).contains("broken.wav"), "{:#}", error);
+        let missing = dir.join("missing");
+        assert!(SoundPack::load(SoundStyle::Chimes, Some(&missing), &[]).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tones_replace_lock_and_unlock_unless_a_file_is_given() {
+        let samples = |pack: &SoundPack, chime| pack.source(chime).unwrap().count();
+        let tones = SoundPack::load(SoundStyle::Tones, None, &[]).unwrap();
+        assert_eq!(
+            samples(&tones, Chime::Activate(2)),
+            tones::locked(2).count()
+        );
+        assert_eq!(
+            samples(&tones, Chime::Deactivate(3)),
+            tones::released(3).count()
+        );
+        // The monitor beeps don't depend on the style.
+        assert_eq!(samples(&tones, Chime::Monitor(2)), tones::beeps(2).count());
+        let chimes = SoundPack::embedded().unwrap();
+        assert_ne!(
+            samples(&chimes, Chime::Activate(2)),
+            samples(&tones, Chime::Activate(2))
+        );
+    }
+
+    #[test]
+    fn wav_output_writes_every_chime_in_full() {
+        let path = std::env::temp_dir().join(format!("cursor-lock-{}.wav", std::process::id()));
+        let played = [Chime::Activate(1), Chime::Monitor(2), Chime::Deactivate(1)];
+        let mut wav = RenderedChimes::new(
+            SoundPack::embedded().unwrap(),
+            WavOutput::create(&path).unwrap(),
+        );
+        let memory = MemoryOutput::default();
+        let mut clips = RenderedChimes::new(SoundPack::embedded().unwrap(), memory.clone());
+        for chime in played {
+            wav.render(chime);
+            clips.render(chime);
+        }
+        drop(wav);
+
+        let decoder = Decoder::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!((decoder.channels(), decoder.sample_rate()), (1, 48_000));
+        let samples: Vec<i16> = decoder.collect();
+        let clips = memory.take();
+        assert_eq!(clips.len(), 3);
+        assert_eq!(samples.len(), clips.iter().map(Vec::len).sum::<usize>());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn volumes_scale_each_event() {
+        let pack = SoundPack::embedded()
+            .unwrap()
+            .with_volumes(0.5, &[(SoundEvent::Unlock, 0.5)]);
+        let loudest = |pack: &SoundPack, chime| {
+            pack.source(chime)
+                .unwrap()
+                .fold(0.0f32, |loudest, sample| loudest.max(sample.abs()))
+        };
+        let full = SoundPack::embedded().unwrap();
+        for (chime, scale) in [(Chime::Activate(1), 0.5), (Chime::Deactivate(1), 0.25)] {
+            let expected = loudest(&full, chime) * scale;
+            assert!((loudest(&pack, chime) - expected).abs() < 1e-4);
+        }
+    }
 }
@@ -0,0 +1,76 @@
+use crate::config::KeyMode;
+use clap::Args;
+use clap::Parser;
+use clap::Subcommand;
+use std::path::PathBuf;
+
+/// The running instance reported the cursor as unlocked (`status` only).
+pub const EXIT_UNLOCKED: u8 = 3;
+/// `lock`, `unlock` or `status` found no running instance to talk to.
+pub const EXIT_NOT_RUNNING: u8 = 4;
+
+/// Lock the cursor to a single monitor.
+///
+/// Exit codes: 0 on success (for `status`: locked), 1 on errors, 2 on invalid usage,
+/// 3 when `status` finds the cursor unlocked, 4 when no instance is running.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Read settings from this file instead of the default `cursor-lock/config.toml` in the user's config directory.
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+// Parsed once at startup, so the size of `RunArgs` doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// List the available monitors.
+    List,
+    /// Lock the cursor and listen for the toggle key. This is the default without a subcommand.
+    Run(RunArgs),
+    /// Lock the cursor in the running instance.
+    Lock,
+    /// Release the cursor in the running instance.
+    Unlock,
+    /// Print whether the running instance has the cursor locked.
+    Status,
+}
+
+#[derive(Debug, Default, Args)]
+pub struct RunArgs {
+    /// The monitor to lock to: its number from `list`, its name, or a regex matching its name or description.
+    /// Prompted for when omitted and stdin is a terminal.
+    #[arg(short, long)]
+    pub monitor: Option<String>,
+    /// The toggle key, e.g. `F9`, `L`, `PageUp`, `Num5`, a chord like `ctrl+alt+L`, or a mouse or gamepad
+    /// button like `MouseBack` or `PadA`. Prompted for when omitted and stdin is a terminal.
+    #[arg(short, long)]
+    pub key: Option<String>,
+    /// Registered instead of the toggle key while another application holds it.
+    #[arg(long, value_name = "KEY")]
+    pub fallback_key: Option<String>,
+    /// Whether the toggle key flips the lock on each press, or only changes it while held.
+    #[arg(long, value_enum)]
+    pub mode: Option<KeyMode>,
+    /// Key that moves the lock to the next monitor in `list`.
+    #[arg(long, value_name = "KEY")]
+    pub next_key: Option<String>,
+    /// Key that moves the lock to the previous monitor in `list`.
+    #[arg(long, value_name = "KEY")]
+    pub previous_key: Option<String>,
+    /// Keys that lock to monitors 1, 2, ... in `list`, e.g. `F5,F6,F7`.
+    #[arg(long, value_name = "KEYS", value_delimiter = ',')]
+    pub monitor_keys: Vec<String>,
+    /// Key that, followed by a digit, locks to that monitor; `0` unlocks. E.g. `ScrollLock`.
+    #[arg(long, value_name = "KEY")]
+    pub leader_key: Option<String>,
+    /// Key that mutes or unmutes the chimes.
+    #[arg(long, value_name = "KEY")]
+    pub mute_key: Option<String>,
+    /// Write the chimes to this WAV file instead of playing them, e.g. on machines without audio.
+    #[arg(long, value_name = "FILE")]
+    pub chimes_wav: Option<PathBuf>,
+}
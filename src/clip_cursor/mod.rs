@@ -0,0 +1,43 @@
+#[cfg(target_os = "linux")]
+mod wayland;
+#[cfg(windows)]
+mod win32;
+#[cfg(target_os = "linux")]
+mod x11;
+
+use crate::dpi::CoordinateSpace;
+use crate::rect::Rect;
+use eyre::Result;
+
+/// Confines the cursor to a rectangle, or releases it again.
+pub trait CursorConfinement: Send {
+    /// The coordinate space `confine` expects its rectangle in.
+    fn coordinate_space(&self) -> CoordinateSpace;
+    /// Restricts the cursor to `rect`, replacing any previous confinement.
+    fn confine(&mut self, rect: Rect) -> Result<()>;
+    /// Removes any confinement so the cursor can move freely.
+    fn release(&mut self) -> Result<()>;
+    /// Registers a callback for when the platform grants or revokes the confinement on its own.
+    /// Backends whose confinement can't change behind our back never call it.
+    fn watch(&mut self, _on_change: Box<dyn Fn(bool) + Send>) {}
+    /// Registers a callback for when the cursor runs into the edge of the confinement.
+    /// Backends that can't tell never call it.
+    fn watch_edges(&mut self, _on_hit: Box<dyn Fn() + Send>) {}
+}
+
+/// Returns the confinement backend for the platform we were built for.
+#[cfg(windows)]
+pub fn default_backend() -> Result<Box<dyn CursorConfinement>> {
+    Ok(Box::new(win32::Win32Confinement::new()))
+}
+
+/// Returns the confinement backend for the platform we were built for.
+#[cfg(target_os = "linux")]
+pub fn default_backend() -> Result<Box<dyn CursorConfinement>> {
+    match crate::session::detect() {
+        crate::session::LinuxSession::Wayland => {
+            Ok(Box::new(wayland::WaylandConfinement::connect()?))
+        }
+        crate::session::LinuxSession::X11 => Ok(Box::new(x11::X11Confinement::connect()?)),
+    }
+}
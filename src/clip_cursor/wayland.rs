@@ -0,0 +1,543 @@
+use crate::clip_cursor::CursorConfinement;
+use crate::dpi::CoordinateSpace;
+use crate::rect::Rect;
+use eyre::Result;
+use std::fs::File;
+use std::io::Write;
+use std::os::fd::AsFd;
+use std::os::fd::FromRawFd;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::sync::PoisonError;
+use wayland_client::delegate_noop;
+use wayland_client::protocol::wl_buffer::WlBuffer;
+use wayland_client::protocol::wl_compositor::WlCompositor;
+use wayland_client::protocol::wl_output::WlOutput;
+use wayland_client::protocol::wl_pointer::WlPointer;
+use wayland_client::protocol::wl_region::WlRegion;
+use wayland_client::protocol::wl_registry;
+use wayland_client::protocol::wl_registry::WlRegistry;
+use wayland_client::protocol::wl_seat;
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::protocol::wl_shm;
+use wayland_client::protocol::wl_shm::WlShm;
+use wayland_client::protocol::wl_shm_pool::WlShmPool;
+use wayland_client::protocol::wl_surface::WlSurface;
+use wayland_client::Connection;
+use wayland_client::Dispatch;
+use wayland_client::EventQueue;
+use wayland_client::Proxy;
+use wayland_client::QueueHandle;
+use wayland_client::WEnum;
+use wayland_protocols::wp::pointer_constraints::zv1::client::zwp_confined_pointer_v1::ZwpConfinedPointerV1;
+use wayland_protocols::wp::pointer_constraints::zv1::client::zwp_pointer_constraints_v1::Lifetime;
+use wayland_protocols::wp::pointer_constraints::zv1::client::zwp_pointer_constraints_v1::ZwpPointerConstraintsV1;
+use wayland_protocols::wp::viewporter::client::wp_viewport::WpViewport;
+use wayland_protocols::wp::viewporter::client::wp_viewporter::WpViewporter;
+use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_manager_v1::ZxdgOutputManagerV1;
+use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1;
+use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::ZxdgOutputV1;
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1::Layer;
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1::ZwlrLayerShellV1;
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1;
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::Anchor;
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::KeyboardInteractivity;
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::ZwlrLayerSurfaceV1;
+
+type ConfinementListener = Arc<Mutex<Option<Box<dyn Fn(bool) + Send>>>>;
+
+/// How far in from each edge of the output the overlay takes pointer input, in logical pixels.
+/// The compositor only confines while the pointer is over our surface, so a frame along the edges
+/// is enough to catch it on the way out, and clicks everywhere else reach the windows below.
+const EDGE_WIDTH: i32 = 32;
+
+/// One `wl_output` global and its logical rectangle, once xdg-output has reported it.
+struct Output {
+    /// The registry name, which `global_remove` refers to when the output is unplugged.
+    name: u32,
+    output: WlOutput,
+    xdg_output: Option<ZxdgOutputV1>,
+    rect: Rect,
+}
+
+/// Shared with the event thread, which follows hotplugs and layout changes.
+type Outputs = Arc<Mutex<Vec<Output>>>;
+
+fn lock_outputs(outputs: &Outputs) -> MutexGuard<'_, Vec<Output>> {
+    outputs.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Globals and per-output geometry collected while connecting, plus what the event thread needs.
+#[derive(Default)]
+struct State {
+    compositor: Option<WlCompositor>,
+    shm: Option<WlShm>,
+    seat: Option<WlSeat>,
+    pointer: Option<WlPointer>,
+    layer_shell: Option<ZwlrLayerShellV1>,
+    constraints: Option<ZwpPointerConstraintsV1>,
+    viewporter: Option<WpViewporter>,
+    xdg_output_manager: Option<ZxdgOutputManagerV1>,
+    outputs: Outputs,
+    /// A 1x1 transparent buffer, stretched over the whole output with a viewport.
+    buffer: Option<WlBuffer>,
+    listener: ConfinementListener,
+}
+
+/// The objects backing one active confinement.
+struct ActiveSurface {
+    surface: WlSurface,
+    viewport: WpViewport,
+    layer_surface: ZwlrLayerSurfaceV1,
+    region: WlRegion,
+    confined_pointer: ZwpConfinedPointerV1,
+}
+
+/// Confines the pointer on Wayland with `zwp_pointer_constraints_v1.confine_pointer`.
+///
+/// Compositors only confine the pointer to one of our own surfaces, so each confinement maps a
+/// transparent layer-shell surface over the chosen output and confines the pointer to it.
+pub struct WaylandConfinement {
+    conn: Connection,
+    qh: QueueHandle<State>,
+    compositor: WlCompositor,
+    pointer: WlPointer,
+    layer_shell: ZwlrLayerShellV1,
+    constraints: ZwpPointerConstraintsV1,
+    viewporter: WpViewporter,
+    outputs: Outputs,
+    listener: ConfinementListener,
+    active: Option<ActiveSurface>,
+}
+
+impl WaylandConfinement {
+    /// Connects to the compositor named by `$WAYLAND_DISPLAY` and starts a thread dispatching its events.
+    pub fn connect() -> Result<Self> {
+        let conn = Connection::connect_to_env()?;
+        let mut queue: EventQueue<State> = conn.new_event_queue();
+        let qh = queue.handle();
+        let _registry = conn.display().get_registry(&qh, ());
+        let mut state = State::default();
+
+        // Bind the globals, then let the seat and outputs report their capabilities and geometry.
+        // The xdg-outputs stay alive so later layout changes keep the rectangles up to date.
+        queue.roundtrip(&mut state)?;
+        queue.roundtrip(&mut state)?;
+
+        let missing = |name: &str| eyre::eyre!("The compositor does not support {}", name);
+        let compositor = state
+            .compositor
+            .clone()
+            .ok_or_else(|| missing("wl_compositor"))?;
+        let shm = state.shm.clone().ok_or_else(|| missing("wl_shm"))?;
+        let pointer = state
+            .pointer
+            .clone()
+            .ok_or_else(|| missing("a pointer device"))?;
+        let layer_shell = state
+            .layer_shell
+            .clone()
+            .ok_or_else(|| missing("zwlr_layer_shell_v1"))?;
+        let constraints = state
+            .constraints
+            .clone()
+            .ok_or_else(|| missing("zwp_pointer_constraints_v1"))?;
+        let viewporter = state
+            .viewporter
+            .clone()
+            .ok_or_else(|| missing("wp_viewporter"))?;
+        if state.xdg_output_manager.is_none() {
+            return Err(missing("zxdg_output_manager_v1"));
+        }
+
+        state.buffer = Some(create_transparent_buffer(&shm, &qh)?);
+        let outputs = state.outputs.clone();
+        let listener = state.listener.clone();
+        conn.flush()?;
+
+        std::thread::spawn(move || loop {
+            if let Err(e) = queue.blocking_dispatch(&mut state) {
+                eprintln!("Error dispatching Wayland events: {:?}", e);
+                break;
+            }
+        });
+
+        Ok(Self {
+            conn,
+            qh,
+            compositor,
+            pointer,
+            layer_shell,
+            constraints,
+            viewporter,
+            outputs,
+            listener,
+            active: None,
+        })
+    }
+
+    /// Finds the output whose logical rectangle matches `rect`, or failing that, contains its centre.
+    fn output_for(&self, rect: Rect) -> Result<WlOutput> {
+        let center_x = rect.left + rect.width() / 2;
+        let center_y = rect.top + rect.height() / 2;
+        let outputs = lock_outputs(&self.outputs);
+        outputs
+            .iter()
+            .find(|output| output.rect == rect)
+            .or_else(|| {
+                outputs.iter().find(|output| {
+                    let r = output.rect;
+                    r.left <= center_x
+                        && center_x < r.right
+                        && r.top <= center_y
+                        && center_y < r.bottom
+                })
+            })
+            .map(|output| output.output.clone())
+            .ok_or_else(|| eyre::eyre!("No Wayland output covers {:?}", rect))
+    }
+}
+
+impl CursorConfinement for WaylandConfinement {
+    fn coordinate_space(&self) -> CoordinateSpace {
+        CoordinateSpace::Logical
+    }
+
+    fn confine(&mut self, rect: Rect) -> Result<()> {
+        self.release()?;
+        let output = self.output_for(rect)?;
+
+        let surface = self.compositor.create_surface(&self.qh, ());
+        let viewport = self.viewporter.get_viewport(&surface, &self.qh, ());
+        let layer_surface = self.layer_shell.get_layer_surface(
+            &surface,
+            Some(&output),
+            Layer::Overlay,
+            "cursor-lock".to_string(),
+            &self.qh,
+            (surface.clone(), viewport.clone()),
+        );
+        // Cover the whole output without pushing other surfaces around or taking keyboard focus.
+        layer_surface.set_anchor(Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right);
+        layer_surface.set_exclusive_zone(-1);
+        layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
+        layer_surface.set_size(0, 0);
+
+        // The region is in surface-local coordinates, and the surface covers exactly the output.
+        let region = self.compositor.create_region(&self.qh, ());
+        region.add(0, 0, rect.width(), rect.height());
+        let input = self.compositor.create_region(&self.qh, ());
+        for (x, y, width, height) in edge_frame(rect.width(), rect.height()) {
+            input.add(x, y, width, height);
+        }
+        surface.set_input_region(Some(&input));
+        input.destroy();
+        let confined_pointer = self.constraints.confine_pointer(
+            &surface,
+            &self.pointer,
+            Some(&region),
+            Lifetime::Persistent,
+            &self.qh,
+            (),
+        );
+        // The first commit has no buffer; the buffer is attached once the compositor configures us.
+        surface.commit();
+        self.conn.flush()?;
+
+        self.active = Some(ActiveSurface {
+            surface,
+            viewport,
+            layer_surface,
+            region,
+            confined_pointer,
+        });
+        Ok(())
+    }
+
+    fn release(&mut self) -> Result<()> {
+        if let Some(active) = self.active.take() {
+            active.confined_pointer.destroy();
+            active.region.destroy();
+            active.layer_surface.destroy();
+            active.viewport.destroy();
+            active.surface.destroy();
+            self.conn.flush()?;
+        }
+        Ok(())
+    }
+
+    fn watch(&mut self, on_change: Box<dyn Fn(bool) + Send>) {
+        if let Ok(mut listener) = self.listener.lock() {
+            *listener = Some(on_change);
+        }
+    }
+}
+
+/// The input region of the overlay: strips of [`EDGE_WIDTH`] along each edge of a `width` x
+/// `height` surface, as `(x, y, width, height)`.
+fn edge_frame(width: i32, height: i32) -> [(i32, i32, i32, i32); 4] {
+    let edge = EDGE_WIDTH.min(width / 2).min(height / 2);
+    [
+        (0, 0, width, edge),
+        (0, height - edge, width, edge),
+        (0, 0, edge, height),
+        (width - edge, 0, edge, height),
+    ]
+}
+
+/// Creates a 1x1 fully transparent ARGB buffer backed by an anonymous memfd.
+fn create_transparent_buffer(shm: &WlShm, qh: &QueueHandle<State>) -> Result<WlBuffer> {
+    let fd = unsafe { libc::memfd_create(c"cursor-lock".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(&[0; 4])?;
+
+    let pool = shm.create_pool(file.as_fd(), 4, qh, ());
+    let buffer = pool.create_buffer(0, 1, 1, 4, wl_shm::Format::Argb8888, qh, ());
+    pool.destroy();
+    Ok(buffer)
+}
+
+impl State {
+    fn notify(&self, confined: bool) {
+        if let Ok(listener) = self.listener.lock() {
+            if let Some(listener) = listener.as_ref() {
+                listener(confined);
+            }
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, ()> for State {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let (name, interface, version) = match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } => (name, interface, version),
+            // An unplugged output; the compositor closes any overlay on it, which `Closed` reports.
+            wl_registry::Event::GlobalRemove { name } => {
+                let mut outputs = lock_outputs(&state.outputs);
+                if let Some(index) = outputs.iter().position(|output| output.name == name) {
+                    let output = outputs.remove(index);
+                    if let Some(xdg_output) = output.xdg_output {
+                        xdg_output.destroy();
+                    }
+                    if output.output.version() >= 3 {
+                        output.output.release();
+                    }
+                }
+                return;
+            }
+            _ => return,
+        };
+        match interface.as_str() {
+            "wl_compositor" => state.compositor = Some(registry.bind(name, version.min(4), qh, ())),
+            "wl_shm" => state.shm = Some(registry.bind(name, 1, qh, ())),
+            "wl_seat" if state.seat.is_none() => {
+                state.seat = Some(registry.bind(name, version.min(5), qh, ()))
+            }
+            "wl_output" => {
+                let output: WlOutput = registry.bind(name, version.min(4), qh, ());
+                let xdg_output = state
+                    .xdg_output_manager
+                    .as_ref()
+                    .map(|manager| manager.get_xdg_output(&output, qh, name));
+                lock_outputs(&state.outputs).push(Output {
+                    name,
+                    output,
+                    xdg_output,
+                    rect: Rect::default(),
+                });
+            }
+            "zwlr_layer_shell_v1" => state.layer_shell = Some(registry.bind(name, 1, qh, ())),
+            "zwp_pointer_constraints_v1" => {
+                state.constraints = Some(registry.bind(name, 1, qh, ()))
+            }
+            "wp_viewporter" => state.viewporter = Some(registry.bind(name, 1, qh, ())),
+            "zxdg_output_manager_v1" => {
+                let manager: ZxdgOutputManagerV1 = registry.bind(name, version.min(3), qh, ());
+                // Outputs announced before the manager still need their xdg-output.
+                for output in lock_outputs(&state.outputs).iter_mut() {
+                    output.xdg_output =
+                        Some(manager.get_xdg_output(&output.output, qh, output.name));
+                }
+                state.xdg_output_manager = Some(manager);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for State {
+    fn event(
+        state: &mut Self,
+        seat: &WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(capabilities),
+        } = event
+        {
+            if capabilities.contains(wl_seat::Capability::Pointer) && state.pointer.is_none() {
+                state.pointer = Some(seat.get_pointer(qh, ()));
+            }
+        }
+    }
+}
+
+impl Dispatch<ZxdgOutputV1, u32> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZxdgOutputV1,
+        event: zxdg_output_v1::Event,
+        name: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let mut outputs = lock_outputs(&state.outputs);
+        let Some(output) = outputs.iter_mut().find(|output| output.name == *name) else {
+            return;
+        };
+        let rect = &mut output.rect;
+        match event {
+            zxdg_output_v1::Event::LogicalPosition { x, y } => {
+                *rect = Rect::from_origin_size(x, y, rect.width(), rect.height())
+            }
+            zxdg_output_v1::Event::LogicalSize { width, height } => {
+                *rect = Rect::from_origin_size(rect.left, rect.top, width, height)
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, (WlSurface, WpViewport)> for State {
+    fn event(
+        state: &mut Self,
+        layer_surface: &ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        (surface, viewport): &(WlSurface, WpViewport),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_layer_surface_v1::Event::Configure {
+                serial,
+                width,
+                height,
+            } => {
+                layer_surface.ack_configure(serial);
+                viewport.set_destination(width as i32, height as i32);
+                surface.attach(state.buffer.as_ref(), 0, 0);
+                surface.commit();
+            }
+            // The compositor took our surface away (e.g. the output was unplugged).
+            zwlr_layer_surface_v1::Event::Closed => state.notify(false),
+            _ => {}
+        }
+    }
+}
+
+// The pointer is only confined while it is over the edge frame, so `confined` and `unconfined`
+// come and go all the time; the lock is only gone for good once the overlay is closed.
+delegate_noop!(State: ignore ZwpConfinedPointerV1);
+delegate_noop!(State: ignore WlCompositor);
+delegate_noop!(State: ignore WlShm);
+delegate_noop!(State: ignore WlShmPool);
+delegate_noop!(State: ignore WlBuffer);
+delegate_noop!(State: ignore WlSurface);
+delegate_noop!(State: ignore WlRegion);
+delegate_noop!(State: ignore WlPointer);
+delegate_noop!(State: ignore WlOutput);
+delegate_noop!(State: ignore ZwlrLayerShellV1);
+delegate_noop!(State: ignore ZwpPointerConstraintsV1);
+delegate_noop!(State: ignore WpViewporter);
+delegate_noop!(State: ignore WpViewport);
+delegate_noop!(State: ignore ZxdgOutputManagerV1);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitors::get_monitors;
+    use crate::monitors::Monitor;
+    use std::process::Command;
+    use std::time::Duration;
+
+    #[test]
+    fn only_the_edges_take_pointer_input() {
+        let frame = edge_frame(1920, 1080);
+        let inside = |x: i32, y: i32| {
+            frame
+                .iter()
+                .any(|&(left, top, w, h)| left <= x && x < left + w && top <= y && y < top + h)
+        };
+        for (x, y) in [(0, 540), (1919, 540), (960, 0), (960, 1079), (31, 31)] {
+            assert!(inside(x, y), "({}, {}) should be in the frame", x, y);
+        }
+        for (x, y) in [(960, 540), (32, 32), (1887, 1047)] {
+            assert!(
+                !inside(x, y),
+                "({}, {}) should reach the windows below",
+                x,
+                y
+            );
+        }
+        // Tiny outputs are covered entirely rather than getting negative strips.
+        assert!(edge_frame(40, 40)
+            .iter()
+            .all(|&(_, _, w, h)| w >= 0 && h >= 0));
+    }
+
+    fn swaymsg(args: &[&str]) {
+        let status = Command::new("swaymsg").args(args).status().unwrap();
+        assert!(status.success(), "swaymsg {:?} failed", args);
+        // Give the compositor and our event thread time to catch up.
+        std::thread::sleep(Duration::from_millis(500));
+    }
+
+    fn output(name: &str) -> Option<Monitor> {
+        get_monitors()
+            .into_iter()
+            .find(|monitor| monitor.id.connector.as_deref() == Some(name))
+    }
+
+    #[test]
+    #[ignore = "needs Sway on the headless backend with a pointer, e.g. `WLR_BACKENDS=headless,libinput sway`"]
+    fn hotplugged_and_moved_outputs_can_be_confined_to() {
+        let mut confinement = WaylandConfinement::connect().unwrap();
+        let before: Vec<_> = get_monitors().into_iter().map(|m| m.id).collect();
+        swaymsg(&["create_output"]);
+        let added = get_monitors()
+            .into_iter()
+            .find(|monitor| !before.contains(&monitor.id))
+            .expect("no new output");
+        let name = added.id.connector.clone().unwrap();
+        confinement.confine(added.logical).unwrap();
+
+        swaymsg(&["output", &name, "pos", "20000", "0"]);
+        let moved = output(&name).unwrap();
+        assert_eq!(moved.logical.left, 20000);
+        confinement.confine(moved.logical).unwrap();
+        confinement.release().unwrap();
+
+        swaymsg(&["output", &name, "unplug"]);
+        assert!(output(&name).is_none());
+        let error = confinement.confine(moved.logical).unwrap_err().to_string();
+        assert!(error.contains("No Wayland output covers"), "{}", error);
+    }
+}
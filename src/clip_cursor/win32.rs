@@ -0,0 +1,62 @@
+use crate::clip_cursor::CursorConfinement;
+use crate::dpi::CoordinateSpace;
+use crate::monitors::enable_per_monitor_dpi_awareness;
+use crate::rect::Rect;
+use eyre::Result;
+use windows::Win32::Foundation::RECT;
+use windows::Win32::UI::HiDpi::GetAwarenessFromDpiAwarenessContext;
+use windows::Win32::UI::HiDpi::GetDpiForSystem;
+use windows::Win32::UI::HiDpi::GetThreadDpiAwarenessContext;
+use windows::Win32::UI::HiDpi::DPI_AWARENESS_UNAWARE;
+use windows::Win32::UI::WindowsAndMessaging::ClipCursor;
+
+/// Confines the cursor using `ClipCursor`.
+pub struct Win32Confinement {
+    space: CoordinateSpace,
+}
+
+impl Win32Confinement {
+    pub fn new() -> Self {
+        enable_per_monitor_dpi_awareness();
+        // ClipCursor interprets coordinates according to our DPI awareness. If we couldn't become
+        // DPI aware, Windows virtualizes every coordinate by the system DPI.
+        let space = unsafe {
+            if GetAwarenessFromDpiAwarenessContext(GetThreadDpiAwarenessContext())
+                == DPI_AWARENESS_UNAWARE
+            {
+                CoordinateSpace::Uniform(f64::from(GetDpiForSystem()) / 96.0)
+            } else {
+                CoordinateSpace::Physical
+            }
+        };
+        Self { space }
+    }
+}
+
+impl CursorConfinement for Win32Confinement {
+    fn coordinate_space(&self) -> CoordinateSpace {
+        self.space
+    }
+
+    fn confine(&mut self, rect: Rect) -> Result<()> {
+        let rect = RECT {
+            left: rect.left,
+            top: rect.top,
+            right: rect.right,
+            bottom: rect.bottom,
+        };
+        unsafe {
+            // Using Some(&rect) to pass a valid clipping region.
+            ClipCursor(Some(&rect))?;
+        }
+        Ok(())
+    }
+
+    fn release(&mut self) -> Result<()> {
+        unsafe {
+            // Passing None removes any clipping region.
+            ClipCursor(None)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,347 @@
+use crate::clip_cursor::CursorConfinement;
+use crate::dpi::CoordinateSpace;
+use crate::rect::Rect;
+use eyre::Result;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use x11rb::connection::Connection;
+use x11rb::connection::RequestConnection;
+use x11rb::protocol::xfixes;
+use x11rb::protocol::xfixes::BarrierDirections;
+use x11rb::protocol::xfixes::ConnectionExt as _;
+use x11rb::protocol::xinput;
+use x11rb::protocol::xinput::ConnectionExt as _;
+use x11rb::protocol::xproto::ConnectionExt as _;
+use x11rb::protocol::xproto::CreateWindowAux;
+use x11rb::protocol::xproto::EventMask;
+use x11rb::protocol::xproto::GrabMode;
+use x11rb::protocol::xproto::GrabStatus;
+use x11rb::protocol::xproto::Window;
+use x11rb::protocol::xproto::WindowClass;
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::CURRENT_TIME;
+use x11rb::NONE;
+
+/// How many times to retry `GrabPointer` while another client holds a grab.
+const GRAB_ATTEMPTS: u32 = 20;
+/// How long to wait between grab attempts.
+const GRAB_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// How the pointer is kept inside the rectangle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum X11ConfineMethod {
+    /// Four XFixes pointer barriers around the rectangle. Other clients keep receiving input.
+    Barriers,
+    /// An active `GrabPointer` with `confine_to` set to an input-only window covering the rectangle.
+    /// All pointer events go to us while the grab is held, so this is only a fallback.
+    Grab,
+}
+
+/// What is currently holding the pointer in place.
+enum Active {
+    Barriers(Vec<xfixes::Barrier>),
+    Grab(Window),
+}
+
+/// Confines the pointer on an X11 display.
+pub struct X11Confinement {
+    /// Shared with the thread watching for barrier hits.
+    conn: Arc<RustConnection>,
+    root: Window,
+    method: X11ConfineMethod,
+    active: Option<Active>,
+}
+
+impl X11Confinement {
+    /// Connects to the display named by `$DISPLAY`, preferring pointer barriers when XFixes 5 is available.
+    pub fn connect() -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+
+        let has_barriers = conn
+            .extension_information(xfixes::X11_EXTENSION_NAME)?
+            .is_some()
+            && conn.xfixes_query_version(5, 0)?.reply()?.major_version >= 5;
+        let method = if has_barriers {
+            X11ConfineMethod::Barriers
+        } else {
+            X11ConfineMethod::Grab
+        };
+
+        Ok(Self {
+            conn: Arc::new(conn),
+            root,
+            method,
+            active: None,
+        })
+    }
+
+    fn confine_with_barriers(&mut self, rect: Rect) -> Result<()> {
+        // X11 root coordinates are never negative, so anything outside u16 is a caller bug.
+        let left = u16::try_from(rect.left)?;
+        let top = u16::try_from(rect.top)?;
+        let right = u16::try_from(rect.right)?;
+        let bottom = u16::try_from(rect.bottom)?;
+
+        // Move the pointer inside first; barriers only stop it from crossing, they don't pull it in.
+        let pointer = self.conn.query_pointer(self.root)?.reply()?;
+        let x = i32::from(pointer.root_x).clamp(rect.left, rect.right - 1);
+        let y = i32::from(pointer.root_y).clamp(rect.top, rect.bottom - 1);
+        if x != i32::from(pointer.root_x) || y != i32::from(pointer.root_y) {
+            self.conn
+                .warp_pointer(NONE, self.root, 0, 0, 0, 0, x as i16, y as i16)?;
+        }
+
+        // A barrier at x blocks leftward motion at x and rightward motion at x - 1,
+        // so barriers on the exclusive edges keep the pointer inside [left, right).
+        let edges = [
+            (left, top, left, bottom),
+            (right, top, right, bottom),
+            (left, top, right, top),
+            (left, bottom, right, bottom),
+        ];
+        let mut barriers = Vec::with_capacity(edges.len());
+        for (x1, y1, x2, y2) in edges {
+            match self.create_barrier(x1, y1, x2, y2) {
+                Ok(barrier) => barriers.push(barrier),
+                Err(e) => {
+                    // Nothing else knows about the barriers made so far, so delete them here.
+                    for barrier in barriers {
+                        let _ = self.conn.xfixes_delete_pointer_barrier(barrier);
+                    }
+                    let _ = self.conn.flush();
+                    return Err(e);
+                }
+            }
+        }
+        self.active = Some(Active::Barriers(barriers));
+        Ok(())
+    }
+
+    fn create_barrier(&self, x1: u16, y1: u16, x2: u16, y2: u16) -> Result<xfixes::Barrier> {
+        let barrier = self.conn.generate_id()?;
+        self.conn
+            .xfixes_create_pointer_barrier(
+                barrier,
+                self.root,
+                x1,
+                y1,
+                x2,
+                y2,
+                BarrierDirections::default(),
+                &[],
+            )?
+            .check()?;
+        Ok(barrier)
+    }
+
+    /// Asks for XInput 2.3 barrier hit events, which arrive on the connection that made the barriers.
+    fn select_barrier_hits(&self) -> Result<()> {
+        let version = self.conn.xinput_xi_query_version(2, 3)?.reply()?;
+        if (version.major_version, version.minor_version) < (2, 3) {
+            eyre::bail!(
+                "the X server only has XInput {}.{}; barrier hits need 2.3",
+                version.major_version,
+                version.minor_version
+            );
+        }
+        self.conn
+            .xinput_xi_select_events(
+                self.root,
+                &[xinput::EventMask {
+                    deviceid: xinput::Device::ALL_MASTER.into(),
+                    mask: vec![xinput::XIEventMask::BARRIER_HIT],
+                }],
+            )?
+            .check()?;
+        Ok(())
+    }
+
+    fn confine_with_grab(&mut self, rect: Rect) -> Result<()> {
+        // The confine_to window must be viewable, so map an override-redirect input-only window over the rect.
+        let window = self.conn.generate_id()?;
+        self.conn
+            .create_window(
+                0,
+                window,
+                self.root,
+                rect.left as i16,
+                rect.top as i16,
+                rect.width() as u16,
+                rect.height() as u16,
+                0,
+                WindowClass::INPUT_ONLY,
+                0,
+                &CreateWindowAux::new().override_redirect(1),
+            )?
+            .check()?;
+        self.conn.map_window(window)?.check()?;
+
+        for attempt in 1..=GRAB_ATTEMPTS {
+            let reply = self
+                .conn
+                .grab_pointer(
+                    true,
+                    window,
+                    EventMask::NO_EVENT,
+                    GrabMode::ASYNC,
+                    GrabMode::ASYNC,
+                    window,
+                    NONE,
+                    CURRENT_TIME,
+                )?
+                .reply()?;
+            match reply.status {
+                GrabStatus::SUCCESS => {
+                    self.active = Some(Active::Grab(window));
+                    return Ok(());
+                }
+                // Someone else is holding the pointer (e.g. a drag in progress); wait for them to let go.
+                GrabStatus::ALREADY_GRABBED | GrabStatus::FROZEN if attempt < GRAB_ATTEMPTS => {
+                    thread::sleep(GRAB_RETRY_DELAY);
+                }
+                status => {
+                    self.conn.destroy_window(window)?;
+                    self.conn.flush()?;
+                    eyre::bail!(
+                        "GrabPointer failed after {} attempt(s): {:?}",
+                        attempt,
+                        status
+                    );
+                }
+            }
+        }
+        unreachable!("the last attempt either succeeds or bails")
+    }
+}
+
+impl CursorConfinement for X11Confinement {
+    fn coordinate_space(&self) -> CoordinateSpace {
+        // X11 has no compositor-side scaling; RandR geometry is already in device pixels.
+        CoordinateSpace::Physical
+    }
+
+    fn confine(&mut self, rect: Rect) -> Result<()> {
+        self.release()?;
+        match self.method {
+            X11ConfineMethod::Barriers => self.confine_with_barriers(rect)?,
+            X11ConfineMethod::Grab => self.confine_with_grab(rect)?,
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn watch_edges(&mut self, on_hit: Box<dyn Fn() + Send>) {
+        // A grab confines without telling us when the pointer pushes against it.
+        if self.method != X11ConfineMethod::Barriers {
+            return;
+        }
+        if let Err(e) = self.select_barrier_hits() {
+            eprintln!("Can't tell when the cursor hits the edge: {:#}", e);
+            return;
+        }
+        let conn = self.conn.clone();
+        thread::spawn(move || {
+            // Every motion against a barrier is reported, but one push keeps its event id.
+            let mut last_hit = None;
+            while let Ok(event) = conn.wait_for_event() {
+                if let Event::XinputBarrierHit(hit) = event {
+                    if last_hit != Some((hit.barrier, hit.eventid)) {
+                        last_hit = Some((hit.barrier, hit.eventid));
+                        on_hit();
+                    }
+                }
+            }
+        });
+    }
+
+    fn release(&mut self) -> Result<()> {
+        match self.active.take() {
+            Some(Active::Barriers(barriers)) => {
+                for barrier in barriers {
+                    self.conn.xfixes_delete_pointer_barrier(barrier)?;
+                }
+            }
+            Some(Active::Grab(window)) => {
+                self.conn.ungrab_pointer(CURRENT_TIME)?;
+                self.conn.destroy_window(window)?;
+            }
+            None => return Ok(()),
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+    use std::sync::Mutex;
+
+    // There is one pointer per display, so tests that move it take turns.
+    static POINTER: Mutex<()> = Mutex::new(());
+
+    /// Moves the pointer with `xdotool`, which goes through XTest like a real device would.
+    fn move_pointer(x: i32, y: i32) {
+        let status = Command::new("xdotool")
+            .args(["mousemove", &x.to_string(), &y.to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    fn pointer(confinement: &X11Confinement) -> (i32, i32) {
+        let reply = confinement
+            .conn
+            .query_pointer(confinement.root)
+            .unwrap()
+            .reply()
+            .unwrap();
+        (i32::from(reply.root_x), i32::from(reply.root_y))
+    }
+
+    #[test]
+    #[ignore = "needs an X server with XFixes 5 and xdotool, e.g. `xvfb-run cargo test -- --ignored`"]
+    fn pointer_is_kept_inside_the_rect() {
+        let _pointer = POINTER.lock().unwrap();
+        let mut confinement = X11Confinement::connect().unwrap();
+        assert_eq!(confinement.method, X11ConfineMethod::Barriers);
+        let rect = Rect::from_origin_size(100, 100, 200, 150);
+        move_pointer(150, 150);
+        confinement.confine(rect).unwrap();
+
+        // Push past the left, right, top and bottom edges in turn.
+        for (x, y) in [(20, 150), (500, 150), (150, 20), (150, 500)] {
+            move_pointer(x, y);
+            let (x, y) = pointer(&confinement);
+            assert!(
+                (rect.left..rect.right).contains(&x) && (rect.top..rect.bottom).contains(&y),
+                "({}, {}) is outside {:?}",
+                x,
+                y,
+                rect
+            );
+        }
+        confinement.release().unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs an X server with XFixes 5 and xdotool, e.g. `xvfb-run cargo test -- --ignored`"]
+    fn pointer_moves_freely_after_release() {
+        let _pointer = POINTER.lock().unwrap();
+        let mut confinement = X11Confinement::connect().unwrap();
+        move_pointer(150, 150);
+        confinement
+            .confine(Rect::from_origin_size(100, 100, 200, 150))
+            .unwrap();
+        confinement.release().unwrap();
+
+        for (x, y) in [(20, 150), (500, 150), (150, 20), (150, 500)] {
+            move_pointer(x, y);
+            assert_eq!(pointer(&confinement), (x, y));
+        }
+    }
+}
@@ -0,0 +1,503 @@
+use crate::chimes::SoundEvent;
+use crate::chimes::SoundPack;
+use crate::chimes::SoundStyle;
+use crate::hotkeys::gesture::GestureAction;
+use crate::hotkeys::gesture::Gestures;
+use crate::monitors::MonitorSelector;
+use crate::toggle_key::parse_toggle_key;
+use crate::toggle_key::Chord;
+use eyre::Context;
+use eyre::Result;
+use serde::Deserialize;
+use serde::Deserializer;
+use std::num::NonZeroU64;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Settings read from `config.toml`. Every field is optional; command-line arguments win over it,
+/// and prompts only run for what neither of them answers.
+///
+/// ```toml
+/// monitor = "MSI3CA9@DP-1" # same selectors as `run --monitor`
+/// key = "ctrl+alt+L"      # same keys as `run --key`
+/// mode = "toggle"         # or "hold-to-unlock" / "hold-to-lock" / "gestures", like `run --mode`
+///
+/// [hotkeys]
+/// fallback = "ctrl+alt+F9" # used while another application holds `key`
+/// next = "F10"            # move the lock to the next monitor
+/// previous = "F11"        # ...or the previous one
+/// monitors = ["F5", "F6"] # lock to monitor 1, 2, ...
+/// leader = "ScrollLock"   # then 1-9 locks to that monitor, 0 unlocks
+/// leader_timeout_ms = 1500
+/// mute = "ctrl+alt+M"     # mute or unmute the chimes
+///
+/// [gestures]              # for mode = "gestures"
+/// tap = "toggle"          # "toggle", "next-monitor", "previous-monitor", "exit" or "nothing"
+/// double_tap = "next-monitor"
+/// long_press = "exit"
+/// double_tap_ms = 300
+/// long_press_ms = 800
+///
+/// [sounds]
+/// enabled = true
+/// style = "tones"         # "chimes" (default), or beeps that rise to lock and fall to unlock
+/// pack = "sounds/retro"   # a directory of lock.wav, unlock.ogg, monitor.flac, reapplied.mp3, edge.*, error.*
+/// edge = "thud.wav"       # a single event's sound, winning over the pack
+/// volume = 0.8            # every chime; 1.0 plays sounds as recorded
+/// quiet_apps = ["obs64.exe", "obs"] # no chimes while these are focused
+///
+/// [sounds.volumes]        # per event, on top of `volume`
+/// monitor = 0.5
+///
+/// [focus]
+/// delay_ms = 250          # wait before reapplying after the foreground window changes
+///
+/// [reapply]
+/// policy = "interval"     # "focus-change" (default), "interval" or "never"
+/// interval_ms = 1000      # for "interval"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub monitor: Option<MonitorSelector>,
+    #[serde(deserialize_with = "deserialize_key")]
+    pub key: Option<Chord>,
+    pub mode: Option<KeyMode>,
+    pub hotkeys: HotkeysConfig,
+    pub gestures: GesturesConfig,
+    pub sounds: SoundsConfig,
+    pub focus: FocusConfig,
+    pub reapply: ReapplyConfig,
+}
+
+/// How the toggle key drives the lock.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyMode {
+    /// Each press flips the lock.
+    #[default]
+    Toggle,
+    /// The cursor is free while the key is held and locked again when it is released.
+    HoldToUnlock,
+    /// The cursor is locked while the key is held and freed again when it is released.
+    HoldToLock,
+    /// Taps, double taps and long presses each do their own thing, see [`GesturesConfig`].
+    Gestures,
+}
+
+/// Keys for switching monitors, and a stand-in for the toggle `key`.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HotkeysConfig {
+    /// Registered instead of the toggle `key` while another application holds it.
+    #[serde(deserialize_with = "deserialize_key")]
+    pub fallback: Option<Chord>,
+    #[serde(deserialize_with = "deserialize_key")]
+    pub next: Option<Chord>,
+    #[serde(deserialize_with = "deserialize_key")]
+    pub previous: Option<Chord>,
+    #[serde(deserialize_with = "deserialize_keys")]
+    pub monitors: Vec<Chord>,
+    /// Followed by a digit, locks to that monitor; `0` unlocks.
+    #[serde(deserialize_with = "deserialize_key")]
+    pub leader: Option<Chord>,
+    /// How long the leader waits for its digit.
+    pub leader_timeout_ms: NonZeroU64,
+    /// Mutes or unmutes the chimes.
+    #[serde(deserialize_with = "deserialize_key")]
+    pub mute: Option<Chord>,
+}
+
+impl Default for HotkeysConfig {
+    fn default() -> Self {
+        Self {
+            fallback: None,
+            next: None,
+            previous: None,
+            monitors: Vec::new(),
+            leader: None,
+            leader_timeout_ms: NonZeroU64::new(1500).unwrap(),
+            mute: None,
+        }
+    }
+}
+
+impl HotkeysConfig {
+    pub fn leader_timeout(&self) -> Duration {
+        Duration::from_millis(self.leader_timeout_ms.get())
+    }
+}
+
+/// What each gesture on the toggle key does in `gestures` mode.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GesturesConfig {
+    pub tap: GestureAction,
+    pub double_tap: GestureAction,
+    pub long_press: GestureAction,
+    /// How soon after a tap the second one must start to count as a double tap.
+    pub double_tap_ms: NonZeroU64,
+    /// How long the key must be held to count as a long press.
+    pub long_press_ms: NonZeroU64,
+}
+
+impl Default for GesturesConfig {
+    fn default() -> Self {
+        Self {
+            tap: GestureAction::Toggle,
+            double_tap: GestureAction::NextMonitor,
+            long_press: GestureAction::Exit,
+            double_tap_ms: NonZeroU64::new(300).unwrap(),
+            long_press_ms: NonZeroU64::new(800).unwrap(),
+        }
+    }
+}
+
+impl GesturesConfig {
+    pub fn gestures(&self) -> Gestures {
+        Gestures {
+            tap: self.tap,
+            double_tap: self.double_tap,
+            long_press: self.long_press,
+            double_tap_window: Duration::from_millis(self.double_tap_ms.get()),
+            long_press_threshold: Duration::from_millis(self.long_press_ms.get()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SoundsConfig {
+    /// Play the lock/unlock chimes.
+    pub enabled: bool,
+    /// What lock and unlock sound like without a file for them.
+    pub style: SoundStyle,
+    /// A directory with a sound for some events, named after the event. Relative paths here and
+    /// below are relative to the config file.
+    pub pack: Option<PathBuf>,
+    pub lock: Option<PathBuf>,
+    pub unlock: Option<PathBuf>,
+    pub monitor: Option<PathBuf>,
+    pub reapplied: Option<PathBuf>,
+    pub edge: Option<PathBuf>,
+    pub error: Option<PathBuf>,
+    /// Scales every chime, from 0.0 to 2.0.
+    #[serde(deserialize_with = "deserialize_volume")]
+    pub volume: f32,
+    pub volumes: VolumesConfig,
+    /// Executable names, e.g. `obs64.exe` or `obs`, that keep chimes quiet while focused.
+    pub quiet_apps: Vec<String>,
+}
+
+/// Per-event volumes, on top of [`SoundsConfig::volume`].
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VolumesConfig {
+    #[serde(deserialize_with = "deserialize_event_volume")]
+    pub lock: Option<f32>,
+    #[serde(deserialize_with = "deserialize_event_volume")]
+    pub unlock: Option<f32>,
+    #[serde(deserialize_with = "deserialize_event_volume")]
+    pub monitor: Option<f32>,
+    #[serde(deserialize_with = "deserialize_event_volume")]
+    pub reapplied: Option<f32>,
+    #[serde(deserialize_with = "deserialize_event_volume")]
+    pub edge: Option<f32>,
+    #[serde(deserialize_with = "deserialize_event_volume")]
+    pub error: Option<f32>,
+}
+
+impl Default for SoundsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            style: SoundStyle::default(),
+            pack: None,
+            lock: None,
+            unlock: None,
+            monitor: None,
+            reapplied: None,
+            edge: None,
+            error: None,
+            volume: 1.0,
+            volumes: VolumesConfig::default(),
+            quiet_apps: Vec::new(),
+        }
+    }
+}
+
+impl SoundsConfig {
+    /// Makes relative paths relative to `dir`, the config file's directory.
+    fn resolve(&mut self, dir: &Path) {
+        let paths = [
+            &mut self.pack,
+            &mut self.lock,
+            &mut self.unlock,
+            &mut self.monitor,
+            &mut self.reapplied,
+            &mut self.edge,
+            &mut self.error,
+        ];
+        for path in paths.into_iter().flatten() {
+            *path = dir.join(&*path);
+        }
+    }
+
+    /// Loads and decodes the configured sounds over those of the style.
+    pub fn sound_pack(&self) -> Result<SoundPack> {
+        let files: Vec<(SoundEvent, PathBuf)> = [
+            (SoundEvent::Lock, &self.lock),
+            (SoundEvent::Unlock, &self.unlock),
+            (SoundEvent::Monitor, &self.monitor),
+            (SoundEvent::Reapplied, &self.reapplied),
+            (SoundEvent::Edge, &self.edge),
+            (SoundEvent::Error, &self.error),
+        ]
+        .into_iter()
+        .filter_map(|(event, path)| Some((event, path.clone()?)))
+        .collect();
+        let volumes: Vec<(SoundEvent, f32)> = [
+            (SoundEvent::Lock, self.volumes.lock),
+            (SoundEvent::Unlock, self.volumes.unlock),
+            (SoundEvent::Monitor, self.volumes.monitor),
+            (SoundEvent::Reapplied, self.volumes.reapplied),
+            (SoundEvent::Edge, self.volumes.edge),
+            (SoundEvent::Error, self.volumes.error),
+        ]
+        .into_iter()
+        .filter_map(|(event, volume)| Some((event, volume?)))
+        .collect();
+        Ok(SoundPack::load(self.style, self.pack.as_deref(), &files)?
+            .with_volumes(self.volume, &volumes))
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FocusConfig {
+    /// How long to wait after a foreground change before reapplying the lock,
+    /// for games that reset the clip while they start up.
+    pub delay_ms: u64,
+}
+
+impl FocusConfig {
+    pub fn delay(&self) -> Duration {
+        Duration::from_millis(self.delay_ms)
+    }
+}
+
+/// When the lock is reapplied while it is enabled.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReapplyPolicy {
+    /// Whenever the foreground window changes.
+    #[default]
+    FocusChange,
+    /// On foreground changes and on a fixed interval.
+    Interval,
+    /// Never; the lock is only applied when toggled on.
+    Never,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReapplyConfig {
+    pub policy: ReapplyPolicy,
+    pub interval_ms: NonZeroU64,
+}
+
+impl Default for ReapplyConfig {
+    fn default() -> Self {
+        Self {
+            policy: ReapplyPolicy::default(),
+            interval_ms: NonZeroU64::new(1000).unwrap(),
+        }
+    }
+}
+
+impl ReapplyConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.get())
+    }
+}
+
+/// Takes a selector string, or a bare monitor number like `monitor = 2`.
+impl<'de> Deserialize<'de> for MonitorSelector {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SelectorVisitor;
+
+        impl serde::de::Visitor<'_> for SelectorVisitor {
+            type Value = MonitorSelector;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a monitor number, name, id or regex")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, selector: &str) -> Result<Self::Value, E> {
+                Ok(MonitorSelector::new(selector))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, index: u64) -> Result<Self::Value, E> {
+                Ok(MonitorSelector::new(index.to_string()))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, index: i64) -> Result<Self::Value, E> {
+                match u64::try_from(index) {
+                    Ok(index) => self.visit_u64(index),
+                    Err(_) => Err(E::invalid_value(
+                        serde::de::Unexpected::Signed(index),
+                        &self,
+                    )),
+                }
+            }
+        }
+
+        deserializer.deserialize_any(SelectorVisitor)
+    }
+}
+
+fn deserialize_volume<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    let volume = f32::deserialize(deserializer)?;
+    if !(0.0..=2.0).contains(&volume) {
+        return Err(serde::de::Error::custom(format!(
+            "the volume must be between 0.0 and 2.0, not {}",
+            volume
+        )));
+    }
+    Ok(volume)
+}
+
+fn deserialize_event_volume<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<f32>, D::Error> {
+    deserialize_volume(deserializer).map(Some)
+}
+
+fn deserialize_key<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Chord>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    parse_toggle_key(&name)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+fn deserialize_keys<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Chord>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|name| parse_toggle_key(name).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+/// `$XDG_CONFIG_HOME/cursor-lock/config.toml` on Linux, `%APPDATA%\cursor-lock\config.toml` on Windows.
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("cursor-lock").join("config.toml"))
+}
+
+impl Config {
+    /// Parses a config file's contents. Errors point at the offending line.
+    pub fn parse(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Loads `path`, or the default path when `None`. A missing default file yields the defaults,
+    /// but a missing file that was asked for explicitly is an error.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => {
+                return Ok(Self::default())
+            }
+            Err(e) => return Err(e).wrap_err_with(|| format!("Failed to read {}", path.display())),
+        };
+        let mut config = Self::parse(&contents)
+            .wrap_err_with(|| format!("Invalid config file {}", path.display()))?;
+        if let Some(dir) = path.parent() {
+            config.sounds.resolve(dir);
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The error for `contents`, with its causes.
+    fn error(contents: &str) -> String {
+        format!("{:#}", Config::parse(contents).unwrap_err())
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_line() {
+        let bad_key = error("monitor = \"1\"\nkey = \"hyper+L\"\n");
+        assert!(bad_key.contains("line 2"), "{}", bad_key);
+        assert!(bad_key.contains("hyper"), "{}", bad_key);
+
+        let bad_next = error("key = \"F9\"\n\n[hotkeys]\nnext = \"F99\"\n");
+        assert!(bad_next.contains("line 4"), "{}", bad_next);
+        assert!(bad_next.contains("F99"), "{}", bad_next);
+
+        let unknown = error("[sounds]\nenabled = true\nvolumn = 0.5\n");
+        assert!(unknown.contains("line 3"), "{}", unknown);
+        assert!(unknown.contains("volumn"), "{}", unknown);
+    }
+
+    #[test]
+    fn monitors_can_be_numbers_or_strings() {
+        for contents in ["monitor = 2", "monitor = \"2\""] {
+            let config = Config::parse(contents).unwrap();
+            assert_eq!(
+                format!("{:?}", config.monitor.unwrap()),
+                "MonitorSelector(\"2\")"
+            );
+        }
+        assert!(error("monitor = -1").contains("line 1"));
+    }
+
+    #[test]
+    fn volumes_out_of_range_point_at_their_line() {
+        let volume = error("[sounds]\nvolume = 2.5\n");
+        assert!(volume.contains("line 2"), "{}", volume);
+        assert!(volume.contains("between 0.0 and 2.0"), "{}", volume);
+        let edge = error("[sounds]\nvolume = 1\n\n[sounds.volumes]\nedge = -0.5\n");
+        assert!(edge.contains("line 5"), "{}", edge);
+        let config = Config::parse("[sounds.volumes]\nmonitor = 0.5\n").unwrap();
+        assert_eq!(config.sounds.volumes.monitor, Some(0.5));
+        assert_eq!(config.sounds.volumes.edge, None);
+    }
+
+    #[test]
+    fn zero_leader_timeout_is_rejected() {
+        let zero = error("[hotkeys]\nleader_timeout_ms = 0\n");
+        assert!(zero.contains("line 2"), "{}", zero);
+        let config = Config::parse("[hotkeys]\nleader_timeout_ms = 900\n").unwrap();
+        assert_eq!(config.hotkeys.leader_timeout(), Duration::from_millis(900));
+    }
+
+    #[test]
+    fn sound_paths_are_relative_to_the_config_file() {
+        let dir = std::env::temp_dir().join(format!("cursor-lock-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let absolute = dir.join("elsewhere").join("error.wav");
+        let contents = format!(
+            "[sounds]\npack = \"retro\"\nlock = \"sounds/lock.wav\"\nerror = {:?}\n",
+            absolute
+        );
+        std::fs::write(&path, contents).unwrap();
+
+        let sounds = Config::load(Some(&path)).unwrap().sounds;
+        assert_eq!(sounds.pack, Some(dir.join("retro")));
+        assert_eq!(sounds.lock, Some(dir.join("sounds").join("lock.wav")));
+        assert_eq!(sounds.error, Some(absolute));
+        assert_eq!(sounds.unlock, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,862 @@
+use crate::chimes::Chime;
+use crate::chimes::ChimePlayer;
+use crate::clip_cursor::CursorConfinement;
+use crate::dpi;
+use crate::monitors::Monitor;
+use crate::toggle_key::Chord;
+use eyre::Result;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+
+/// Something the hotkey, focus, IPC or signal sources want the controller to do.
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// The toggle hotkey was pressed.
+    Toggle,
+    /// `cursor-lock lock`.
+    Lock,
+    /// `cursor-lock unlock`.
+    Unlock,
+    /// Only reports the current state, for `cursor-lock status`.
+    Status,
+    /// Another window may have reset the clip; reapply it if locked.
+    Reapply,
+    /// The foreground window changed, which may have reset the clip. Like [`Command::Reapply`],
+    /// but chimes when there was a lock to reapply.
+    FocusChanged,
+    /// The cursor ran into the edge of the monitor it is locked to.
+    EdgeHit,
+    /// Mute the chimes, or unmute them.
+    ToggleMute,
+    /// The foreground window now belongs to this application, if it could be told.
+    /// Chimes stay quiet while it is one of the quiet apps.
+    ForegroundApp(Option<String>),
+    /// Move the lock to the next monitor in `cursor-lock list`, wrapping around.
+    NextMonitor,
+    /// Move the lock to the previous monitor in `cursor-lock list`, wrapping around.
+    PreviousMonitor,
+    /// Lock to the monitor at this position in `cursor-lock list`, counting from 0.
+    LockToMonitor(usize),
+    /// A momentary key went down: lock (`true`) or unlock (`false`) until [`Command::EndHold`].
+    Hold(bool),
+    /// The momentary key was released: go back to the state from before the hold.
+    EndHold,
+    /// The leader key was pressed (`true`), or timed out without a digit following it.
+    LeaderArmed(bool),
+    /// A hotkey couldn't be registered, e.g. because another application holds its key.
+    HotkeyUnavailable {
+        /// Which key, e.g. "the toggle key F9".
+        hotkey: String,
+        reason: String,
+        /// Keys that were free when this was reported.
+        suggestions: Vec<Chord>,
+        /// Whether it is registered as soon as the other application lets go.
+        retrying: bool,
+    },
+    /// A hotkey is registered after all, on retry or as the fallback `instead_of` another key.
+    HotkeyRegistered {
+        hotkey: String,
+        instead_of: Option<Chord>,
+    },
+    /// The platform granted (`true`) or revoked (`false`) the confinement on its own.
+    ConfinementChanged(bool),
+    /// Release the cursor and stop the controller, e.g. on Ctrl+C.
+    Shutdown,
+}
+
+struct Message {
+    command: Command,
+    /// Receives whether the cursor is locked once the command has been handled.
+    reply: Option<Sender<Result<bool, String>>>,
+}
+
+/// A cloneable way for event sources to reach the controller running on another thread.
+#[derive(Clone)]
+pub struct ControllerHandle(Sender<Message>);
+
+impl ControllerHandle {
+    /// Queues `command` without waiting for it to be handled.
+    pub fn send(&self, command: Command) {
+        // The controller only goes away when the process is exiting, so there's no one to tell.
+        let _ = self.0.send(Message {
+            command,
+            reply: None,
+        });
+    }
+
+    /// Queues `command` and waits for it to be handled. Returns whether the cursor is locked afterwards.
+    pub fn request(&self, command: Command) -> Result<bool> {
+        let (reply, response) = mpsc::channel();
+        self.0
+            .send(Message {
+                command,
+                reply: Some(reply),
+            })
+            .map_err(|_| eyre::eyre!("The controller has stopped"))?;
+        match response.recv() {
+            Ok(result) => result.map_err(|message| eyre::eyre!(message)),
+            Err(_) => eyre::bail!("The controller has stopped"),
+        }
+    }
+}
+
+/// Owns the lock state and is the only place that confines, releases or plays chimes,
+/// so the event sources can't race each other.
+pub struct Controller {
+    confinement: Box<dyn CursorConfinement>,
+    chimes: Box<dyn ChimePlayer>,
+    /// The monitors found at startup, in `cursor-lock list` order.
+    monitors: Vec<Monitor>,
+    /// Position of the monitor we lock to in `monitors`.
+    target: usize,
+    enabled: bool,
+    /// Whether we last asked the platform to lock, as opposed to what it currently grants.
+    requested: bool,
+    /// The state to restore when a momentary key is released, while one is held.
+    held: Option<bool>,
+    muted: bool,
+    /// Applications that keep the chimes quiet while focused.
+    quiet_apps: Vec<String>,
+    /// The quiet app that is focused right now, if any.
+    quiet_for: Option<String>,
+    receiver: Receiver<Message>,
+    handle: ControllerHandle,
+}
+
+impl Controller {
+    /// Creates an unlocked controller for `monitors[target]`. Send [`Command::Lock`] to engage it.
+    pub fn new(
+        mut confinement: Box<dyn CursorConfinement>,
+        chimes: Box<dyn ChimePlayer>,
+        monitors: Vec<Monitor>,
+        target: usize,
+    ) -> Self {
+        assert!(target < monitors.len(), "target monitor out of range");
+        let (sender, receiver) = mpsc::channel();
+        let handle = ControllerHandle(sender);
+        // Keep our state in sync with what the platform actually granted,
+        // so the next toggle re-engages rather than "releasing" a confinement we no longer hold.
+        let watcher = handle.clone();
+        confinement.watch(Box::new(move |granted| {
+            watcher.send(Command::ConfinementChanged(granted));
+        }));
+        let watcher = handle.clone();
+        confinement.watch_edges(Box::new(move || watcher.send(Command::EdgeHit)));
+        Self {
+            confinement,
+            chimes,
+            monitors,
+            target,
+            enabled: false,
+            requested: false,
+            held: None,
+            muted: false,
+            quiet_apps: Vec::new(),
+            quiet_for: None,
+            receiver,
+            handle,
+        }
+    }
+
+    /// Keeps the chimes quiet while one of `apps` is focused, as reported by [`Command::ForegroundApp`].
+    pub fn set_quiet_apps(&mut self, apps: Vec<String>) {
+        self.quiet_apps = apps;
+    }
+
+    pub fn handle(&self) -> ControllerHandle {
+        self.handle.clone()
+    }
+
+    /// Handles commands until [`Command::Shutdown`] arrives.
+    pub fn run(mut self) {
+        while let Ok(Message { command, reply }) = self.receiver.recv() {
+            let shutdown = matches!(command, Command::Shutdown);
+            let result = self.handle_command(command).map_err(|e| format!("{:#}", e));
+            if let Err(e) = &result {
+                eprintln!("Error: {}", e);
+                self.chime(Chime::Error);
+            }
+            if let Some(reply) = reply {
+                let _ = reply.send(result);
+            }
+            if shutdown {
+                break;
+            }
+        }
+    }
+
+    /// Applies one command and returns whether the cursor is locked afterwards.
+    pub fn handle_command(&mut self, command: Command) -> Result<bool> {
+        match command {
+            Command::Toggle => {
+                if self.enabled {
+                    println!("Hotkey pressed: deactivating clipping.");
+                } else {
+                    println!("Hotkey pressed: activating clipping.");
+                }
+                self.set_locked(!self.enabled)?;
+            }
+            Command::Lock => self.set_locked(true)?,
+            Command::Unlock => self.set_locked(false)?,
+            Command::Status => {}
+            Command::Reapply => {
+                if self.enabled {
+                    self.confinement.confine(self.rect())?;
+                }
+            }
+            Command::FocusChanged => {
+                if self.enabled {
+                    self.confinement.confine(self.rect())?;
+                    self.chime(Chime::Reapplied);
+                }
+            }
+            Command::EdgeHit => {
+                if self.enabled {
+                    self.chime(Chime::EdgeHit);
+                }
+            }
+            Command::ToggleMute => {
+                self.muted = !self.muted;
+                if self.muted {
+                    println!("Chimes muted.");
+                } else {
+                    println!("Chimes unmuted.");
+                }
+            }
+            Command::ForegroundApp(app) => {
+                let quiet_for = app.filter(|app| {
+                    self.quiet_apps
+                        .iter()
+                        .any(|quiet| crate::focus::is_same_app(app, quiet))
+                });
+                match (&self.quiet_for, &quiet_for) {
+                    (None, Some(app)) => println!("{} is focused: chimes are quiet.", app),
+                    (Some(_), None) => println!("Chimes are back on."),
+                    _ => {}
+                }
+                self.quiet_for = quiet_for;
+            }
+            Command::NextMonitor => {
+                self.retarget((self.target + 1) % self.monitors.len(), false)?
+            }
+            Command::PreviousMonitor => {
+                let count = self.monitors.len();
+                self.retarget((self.target + count - 1) % count, false)?
+            }
+            Command::LockToMonitor(index) => {
+                if index >= self.monitors.len() {
+                    eyre::bail!(
+                        "There is no monitor {} (1-{})",
+                        index + 1,
+                        self.monitors.len()
+                    );
+                }
+                self.retarget(index, true)?;
+            }
+            Command::Hold(locked) => {
+                if self.held.is_none() {
+                    self.held = Some(self.enabled);
+                }
+                if self.enabled != locked {
+                    if locked {
+                        println!("Hotkey held: activating clipping.");
+                    } else {
+                        println!("Hotkey held: deactivating clipping.");
+                    }
+                }
+                self.set_locked(locked)?;
+            }
+            Command::EndHold => {
+                if let Some(locked) = self.held.take() {
+                    if self.enabled != locked {
+                        if locked {
+                            println!("Hotkey released: activating clipping.");
+                        } else {
+                            println!("Hotkey released: deactivating clipping.");
+                        }
+                    }
+                    self.set_locked(locked)?;
+                }
+            }
+            Command::LeaderArmed(true) => {
+                println!("Leader pressed: 1-9 locks to that monitor, 0 unlocks.");
+                self.chime(Chime::Armed);
+            }
+            Command::LeaderArmed(false) => println!("Leader timed out."),
+            Command::HotkeyUnavailable {
+                hotkey,
+                reason,
+                suggestions,
+                retrying,
+            } => {
+                eprintln!("Can't register {}: {}.", hotkey, reason);
+                if !suggestions.is_empty() {
+                    let names: Vec<String> = suggestions.iter().map(Chord::to_string).collect();
+                    eprintln!("These keys are free right now: {}", names.join(", "));
+                }
+                if retrying {
+                    eprintln!(
+                        "It will be registered as soon as the other application releases it."
+                    );
+                }
+                if self.enabled {
+                    eprintln!("Meanwhile `cursor-lock unlock` or Ctrl+C releases the cursor.");
+                }
+            }
+            Command::HotkeyRegistered { hotkey, instead_of } => match instead_of {
+                Some(original) => println!("Using {} instead of {}.", hotkey, original),
+                None => println!("Registered {} after all.", hotkey),
+            },
+            Command::ConfinementChanged(granted) => {
+                // A grant can be queued before a release and handled after it; that one is stale.
+                if granted && !self.requested {
+                    return Ok(self.enabled);
+                }
+                if self.enabled != granted {
+                    self.enabled = granted;
+                    if granted {
+                        println!("The cursor is now confined.");
+                    } else {
+                        println!("The cursor was released by the system.");
+                    }
+                }
+            }
+            Command::Shutdown => self.set_locked(false)?,
+        }
+        Ok(self.enabled)
+    }
+
+    /// Locks or unlocks with the matching chime. Does nothing if already in the requested state.
+    /// A lock the platform revoked is still released, since it may grant it again on its own.
+    fn set_locked(&mut self, locked: bool) -> Result<()> {
+        if self.enabled == locked && self.requested == locked {
+            return Ok(());
+        }
+        if locked {
+            self.confinement.confine(self.rect())?;
+        } else {
+            self.confinement.release()?;
+        }
+        self.enabled = locked;
+        self.requested = locked;
+        let monitor = self.target + 1;
+        self.chime(if locked {
+            Chime::Activate(monitor)
+        } else {
+            Chime::Deactivate(monitor)
+        });
+        Ok(())
+    }
+
+    /// Points the lock at `monitors[index]`, moving an active confinement along (or engaging it
+    /// if `lock`), and chimes the monitor's number.
+    fn retarget(&mut self, index: usize, lock: bool) -> Result<()> {
+        let previous = self.target;
+        self.target = index;
+        if self.enabled || lock {
+            if let Err(e) = self.confinement.confine(self.rect()) {
+                self.target = previous;
+                return Err(e);
+            }
+            self.enabled = true;
+            self.requested = true;
+        }
+        let monitor = &self.monitors[index];
+        println!("Switched to monitor {}: {}", index + 1, monitor.name);
+        self.chime(Chime::Monitor(index + 1));
+        Ok(())
+    }
+
+    /// Plays `chime` unless muted or a quiet app is focused.
+    fn chime(&mut self, chime: Chime) {
+        if !self.muted && self.quiet_for.is_none() {
+            self.chimes.play(chime);
+        }
+    }
+
+    fn rect(&self) -> crate::rect::Rect {
+        dpi::clip_rect(
+            &self.monitors[self.target],
+            self.confinement.coordinate_space(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chimes::MemoryOutput;
+    use crate::chimes::RenderedChimes;
+    use crate::chimes::SoundPack;
+    use crate::chimes::SoundStyle;
+    use crate::dpi::CoordinateSpace;
+    use crate::rect::Rect;
+    use crate::tones;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    /// Everything the fakes were asked to do, in order.
+    #[derive(Debug, Clone, PartialEq)]
+    enum Effect {
+        Confine(Rect),
+        Release,
+        Play(Chime),
+    }
+
+    type Log = Arc<Mutex<Vec<Effect>>>;
+
+    struct FakeConfinement {
+        log: Log,
+        fail: bool,
+    }
+
+    impl CursorConfinement for FakeConfinement {
+        fn coordinate_space(&self) -> CoordinateSpace {
+            CoordinateSpace::Physical
+        }
+
+        fn confine(&mut self, rect: Rect) -> Result<()> {
+            if self.fail {
+                eyre::bail!("confinement refused");
+            }
+            self.log.lock().unwrap().push(Effect::Confine(rect));
+            Ok(())
+        }
+
+        fn release(&mut self) -> Result<()> {
+            self.log.lock().unwrap().push(Effect::Release);
+            Ok(())
+        }
+    }
+
+    struct FakeChimes(Log);
+
+    impl ChimePlayer for FakeChimes {
+        fn play(&mut self, chime: Chime) {
+            self.0.lock().unwrap().push(Effect::Play(chime));
+        }
+    }
+
+    fn monitor(x: i32) -> Monitor {
+        let physical = Rect::from_origin_size(x, 0, 1920, 1080);
+        Monitor {
+            name: format!("Monitor at {}", x),
+            description: None,
+            id: Default::default(),
+            physical,
+            logical: physical,
+            scale: 1.0,
+        }
+    }
+
+    fn controller(fail: bool) -> (Controller, Log) {
+        let log = Log::default();
+        let confinement = FakeConfinement {
+            log: log.clone(),
+            fail,
+        };
+        let controller = Controller::new(
+            Box::new(confinement),
+            Box::new(FakeChimes(log.clone())),
+            vec![monitor(-1920), monitor(0), monitor(1920)],
+            1,
+        );
+        (controller, log)
+    }
+
+    fn take(log: &Log) -> Vec<Effect> {
+        std::mem::take(&mut *log.lock().unwrap())
+    }
+
+    #[test]
+    fn toggle_confines_and_releases_with_chimes() {
+        let (mut controller, log) = controller(false);
+        let rect = monitor(0).physical;
+
+        assert!(controller.handle_command(Command::Toggle).unwrap());
+        assert!(!controller.handle_command(Command::Toggle).unwrap());
+        assert_eq!(
+            take(&log),
+            [
+                Effect::Confine(rect),
+                Effect::Play(Chime::Activate(2)),
+                Effect::Release,
+                Effect::Play(Chime::Deactivate(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn lock_and_unlock_are_idempotent() {
+        let (mut controller, log) = controller(false);
+        controller.handle_command(Command::Lock).unwrap();
+        take(&log);
+
+        assert!(controller.handle_command(Command::Lock).unwrap());
+        assert!(controller.handle_command(Command::Status).unwrap());
+        assert_eq!(take(&log), []);
+
+        controller.handle_command(Command::Unlock).unwrap();
+        take(&log);
+        assert!(!controller.handle_command(Command::Unlock).unwrap());
+        assert_eq!(take(&log), []);
+    }
+
+    #[test]
+    fn reapply_is_silent_and_only_while_locked() {
+        let (mut controller, log) = controller(false);
+        controller.handle_command(Command::Reapply).unwrap();
+        assert_eq!(take(&log), []);
+
+        controller.handle_command(Command::Lock).unwrap();
+        take(&log);
+        controller.handle_command(Command::Reapply).unwrap();
+        assert_eq!(take(&log), [Effect::Confine(monitor(0).physical)]);
+    }
+
+    #[test]
+    fn cycling_wraps_and_moves_an_active_lock() {
+        let (mut controller, log) = controller(false);
+        controller.handle_command(Command::NextMonitor).unwrap();
+        assert_eq!(take(&log), [Effect::Play(Chime::Monitor(3))]);
+
+        controller.handle_command(Command::Lock).unwrap();
+        take(&log);
+        controller.handle_command(Command::NextMonitor).unwrap();
+        controller.handle_command(Command::PreviousMonitor).unwrap();
+        assert_eq!(
+            take(&log),
+            [
+                Effect::Confine(monitor(-1920).physical),
+                Effect::Play(Chime::Monitor(1)),
+                Effect::Confine(monitor(1920).physical),
+                Effect::Play(Chime::Monitor(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn reapply_follows_the_new_target() {
+        let (mut controller, log) = controller(false);
+        controller.handle_command(Command::Lock).unwrap();
+        controller.handle_command(Command::PreviousMonitor).unwrap();
+        take(&log);
+        controller.handle_command(Command::Reapply).unwrap();
+        assert_eq!(take(&log), [Effect::Confine(monitor(-1920).physical)]);
+    }
+
+    #[test]
+    fn lock_to_monitor_engages_the_lock() {
+        let (mut controller, log) = controller(false);
+        assert!(controller
+            .handle_command(Command::LockToMonitor(2))
+            .unwrap());
+        assert_eq!(
+            take(&log),
+            [
+                Effect::Confine(monitor(1920).physical),
+                Effect::Play(Chime::Monitor(3))
+            ]
+        );
+
+        assert!(controller
+            .handle_command(Command::LockToMonitor(3))
+            .is_err());
+        assert_eq!(take(&log), []);
+    }
+
+    #[test]
+    fn failed_retarget_keeps_the_old_monitor() {
+        let (mut controller, log) = controller(true);
+        assert!(controller
+            .handle_command(Command::LockToMonitor(0))
+            .is_err());
+        controller.handle_command(Command::NextMonitor).unwrap();
+        assert_eq!(take(&log), [Effect::Play(Chime::Monitor(3))]);
+    }
+
+    #[test]
+    fn locking_and_unlocking_sound_the_monitor_number() {
+        let log = Log::default();
+        let output = MemoryOutput::default();
+        let pack = SoundPack::load(SoundStyle::Tones, None, &[]).unwrap();
+        let mut controller = Controller::new(
+            Box::new(FakeConfinement {
+                log: log.clone(),
+                fail: false,
+            }),
+            Box::new(RenderedChimes::new(pack, output.clone())),
+            vec![monitor(-1920), monitor(0), monitor(1920)],
+            1,
+        );
+
+        controller.handle_command(Command::Lock).unwrap();
+        controller.handle_command(Command::NextMonitor).unwrap();
+        controller.handle_command(Command::Unlock).unwrap();
+        let pitches: Vec<Vec<u32>> = output
+            .take()
+            .iter()
+            .map(|clip| {
+                let pitches = tones::beep_pitches(clip);
+                pitches.iter().map(|hz| hz.round() as u32).collect()
+            })
+            .collect();
+        // Two rising beeps for monitor 2, three even ones for moving to 3, and three falling.
+        let near = |actual: &[u32], expected: &[u32]| {
+            actual.len() == expected.len()
+                && actual
+                    .iter()
+                    .zip(expected)
+                    .all(|(a, e)| a.abs_diff(*e) < 20)
+        };
+        assert_eq!(pitches.len(), 3, "{:?}", pitches);
+        assert!(near(&pitches[0], &[660, 741]), "{:?}", pitches);
+        assert!(near(&pitches[1], &[880, 880, 880]), "{:?}", pitches);
+        assert!(near(&pitches[2], &[832, 741, 660]), "{:?}", pitches);
+    }
+
+    #[test]
+    fn hold_unlocks_until_released() {
+        let (mut controller, log) = controller(false);
+        controller.handle_command(Command::Lock).unwrap();
+        take(&log);
+
+        assert!(!controller.handle_command(Command::Hold(false)).unwrap());
+        assert!(controller.handle_command(Command::EndHold).unwrap());
+        // A stray release without a hold changes nothing.
+        assert!(controller.handle_command(Command::EndHold).unwrap());
+        assert_eq!(
+            take(&log),
+            [
+                Effect::Release,
+                Effect::Play(Chime::Deactivate(2)),
+                Effect::Confine(monitor(0).physical),
+                Effect::Play(Chime::Activate(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn hold_restores_the_state_from_before_it() {
+        let (mut controller, log) = controller(false);
+        assert!(controller.handle_command(Command::Hold(true)).unwrap());
+        assert!(!controller.handle_command(Command::EndHold).unwrap());
+
+        // Holding to unlock while already unlocked leaves it unlocked on release.
+        take(&log);
+        controller.handle_command(Command::Hold(false)).unwrap();
+        assert!(!controller.handle_command(Command::EndHold).unwrap());
+        assert_eq!(take(&log), []);
+    }
+
+    #[test]
+    fn hotkey_problems_leave_the_lock_alone() {
+        let (mut controller, log) = controller(false);
+        controller.handle_command(Command::Lock).unwrap();
+        take(&log);
+
+        let unavailable = Command::HotkeyUnavailable {
+            hotkey: "the toggle key F9".to_string(),
+            reason: "another application has already registered it".to_string(),
+            suggestions: Vec::new(),
+            retrying: true,
+        };
+        assert!(controller.handle_command(unavailable).unwrap());
+        let registered = Command::HotkeyRegistered {
+            hotkey: "the toggle key F9".to_string(),
+            instead_of: None,
+        };
+        assert!(controller.handle_command(registered).unwrap());
+        assert_eq!(take(&log), []);
+    }
+
+    #[test]
+    fn focus_changes_and_edge_hits_only_chime_while_locked() {
+        let (mut controller, log) = controller(false);
+        let rect = monitor(0).physical;
+        controller.handle_command(Command::FocusChanged).unwrap();
+        controller.handle_command(Command::EdgeHit).unwrap();
+        assert_eq!(take(&log), []);
+
+        controller.handle_command(Command::Lock).unwrap();
+        take(&log);
+        controller.handle_command(Command::FocusChanged).unwrap();
+        controller.handle_command(Command::EdgeHit).unwrap();
+        assert_eq!(
+            take(&log),
+            [
+                Effect::Confine(rect),
+                Effect::Play(Chime::Reapplied),
+                Effect::Play(Chime::EdgeHit),
+            ]
+        );
+    }
+
+    #[test]
+    fn mute_and_quiet_apps_silence_chimes_but_not_the_lock() {
+        let (mut controller, log) = controller(false);
+        let rect = monitor(0).physical;
+        controller.set_quiet_apps(vec!["obs64.exe".to_string()]);
+
+        controller.handle_command(Command::ToggleMute).unwrap();
+        controller.handle_command(Command::Lock).unwrap();
+        controller.handle_command(Command::ToggleMute).unwrap();
+        assert_eq!(take(&log), [Effect::Confine(rect)]);
+
+        let focus = |app: &str| Command::ForegroundApp(Some(app.to_string()));
+        controller.handle_command(focus("OBS64")).unwrap();
+        controller.handle_command(Command::Unlock).unwrap();
+        assert_eq!(take(&log), [Effect::Release]);
+
+        controller.handle_command(focus("game.exe")).unwrap();
+        controller.handle_command(Command::Lock).unwrap();
+        assert_eq!(
+            take(&log),
+            [Effect::Confine(rect), Effect::Play(Chime::Activate(2))]
+        );
+    }
+
+    #[test]
+    fn armed_leader_only_chimes() {
+        let (mut controller, log) = controller(false);
+        assert!(!controller
+            .handle_command(Command::LeaderArmed(true))
+            .unwrap());
+        assert!(!controller
+            .handle_command(Command::LeaderArmed(false))
+            .unwrap());
+        assert_eq!(take(&log), [Effect::Play(Chime::Armed)]);
+    }
+
+    #[test]
+    fn revoked_confinement_is_reengaged_by_the_next_toggle() {
+        let (mut controller, log) = controller(false);
+        controller.handle_command(Command::Lock).unwrap();
+        take(&log);
+
+        assert!(!controller
+            .handle_command(Command::ConfinementChanged(false))
+            .unwrap());
+        assert!(controller.handle_command(Command::Toggle).unwrap());
+        assert_eq!(
+            take(&log),
+            [
+                Effect::Confine(monitor(0).physical),
+                Effect::Play(Chime::Activate(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn stale_grant_after_unlock_is_ignored() {
+        let (mut controller, log) = controller(false);
+        controller.handle_command(Command::Lock).unwrap();
+        controller.handle_command(Command::Unlock).unwrap();
+        take(&log);
+
+        assert!(!controller
+            .handle_command(Command::ConfinementChanged(true))
+            .unwrap());
+        assert!(!controller.handle_command(Command::Status).unwrap());
+        // The next toggle locks instead of silently "unlocking".
+        assert!(controller.handle_command(Command::Toggle).unwrap());
+        assert_eq!(
+            take(&log),
+            [
+                Effect::Confine(monitor(0).physical),
+                Effect::Play(Chime::Activate(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn revoked_confinement_counts_again_once_regranted() {
+        let (mut controller, log) = controller(false);
+        controller.handle_command(Command::Lock).unwrap();
+        take(&log);
+
+        controller
+            .handle_command(Command::ConfinementChanged(false))
+            .unwrap();
+        assert!(controller
+            .handle_command(Command::ConfinementChanged(true))
+            .unwrap());
+        assert_eq!(take(&log), []);
+    }
+
+    #[test]
+    fn regrant_after_locking_to_a_monitor_counts() {
+        let (mut controller, log) = controller(false);
+        assert!(controller
+            .handle_command(Command::LockToMonitor(2))
+            .unwrap());
+        take(&log);
+
+        assert!(!controller
+            .handle_command(Command::ConfinementChanged(false))
+            .unwrap());
+        assert!(controller
+            .handle_command(Command::ConfinementChanged(true))
+            .unwrap());
+        assert!(controller.handle_command(Command::Status).unwrap());
+        // So the next toggle releases.
+        assert!(!controller.handle_command(Command::Toggle).unwrap());
+        assert_eq!(
+            take(&log),
+            [Effect::Release, Effect::Play(Chime::Deactivate(3))]
+        );
+    }
+
+    #[test]
+    fn unlock_after_a_revoke_still_releases() {
+        let (mut controller, log) = controller(false);
+        controller.handle_command(Command::Lock).unwrap();
+        controller
+            .handle_command(Command::ConfinementChanged(false))
+            .unwrap();
+        take(&log);
+
+        assert!(!controller.handle_command(Command::Unlock).unwrap());
+        assert_eq!(
+            take(&log),
+            [Effect::Release, Effect::Play(Chime::Deactivate(2))]
+        );
+        // Nothing is left to regrant, and a second unlock does nothing.
+        assert!(!controller
+            .handle_command(Command::ConfinementChanged(true))
+            .unwrap());
+        controller.handle_command(Command::Unlock).unwrap();
+        assert_eq!(take(&log), []);
+    }
+
+    #[test]
+    fn failed_confinement_stays_unlocked_without_a_chime() {
+        let (mut controller, log) = controller(true);
+        assert!(controller.handle_command(Command::Lock).is_err());
+        assert!(!controller.handle_command(Command::Status).unwrap());
+        assert_eq!(take(&log), []);
+    }
+
+    #[test]
+    fn requests_are_answered_from_the_controller_thread() {
+        let (controller, log) = controller(false);
+        let handle = controller.handle();
+        let thread = std::thread::spawn(move || controller.run());
+
+        assert!(handle.request(Command::Lock).unwrap());
+        assert!(handle.request(Command::Status).unwrap());
+        assert!(!handle.request(Command::Shutdown).unwrap());
+        thread.join().unwrap();
+        assert!(handle.request(Command::Status).is_err());
+        assert_eq!(
+            take(&log),
+            [
+                Effect::Confine(monitor(0).physical),
+                Effect::Play(Chime::Activate(2)),
+                Effect::Release,
+                Effect::Play(Chime::Deactivate(2)),
+            ]
+        );
+    }
+}
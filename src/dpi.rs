@@ -0,0 +1,172 @@
+use crate::monitors::Monitor;
+use crate::rect::Rect;
+
+/// The coordinate space a confinement backend expects its rectangle in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoordinateSpace {
+    /// Device pixels, as used by per-monitor-DPI-aware Win32 processes and X11.
+    Physical,
+    /// The compositor's logical layout, as used by Wayland.
+    #[cfg_attr(windows, allow(dead_code))]
+    Logical,
+    /// Physical coordinates divided by one desktop-wide scale factor,
+    /// as seen by a DPI-unaware Win32 process.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Uniform(f64),
+}
+
+/// Converts a monitor's physical rectangle to logical units.
+/// The origin stays put and only the size is divided by the scale, which is how both Windows
+/// and our Wayland backend relate the two spaces.
+pub fn physical_to_logical(physical: Rect, scale: f64) -> Rect {
+    Rect::from_origin_size(
+        physical.left,
+        physical.top,
+        (f64::from(physical.width()) / scale).round() as i32,
+        (f64::from(physical.height()) / scale).round() as i32,
+    )
+}
+
+/// Converts a monitor's logical rectangle to physical pixels. The inverse of [`physical_to_logical`].
+#[cfg_attr(windows, allow(dead_code))]
+pub fn logical_to_physical(logical: Rect, scale: f64) -> Rect {
+    Rect::from_origin_size(
+        logical.left,
+        logical.top,
+        (f64::from(logical.width()) * scale).round() as i32,
+        (f64::from(logical.height()) * scale).round() as i32,
+    )
+}
+
+/// Divides every edge of a physical rectangle by a single scale factor.
+/// Edges are rounded inwards so the result never reaches into a neighbouring monitor.
+pub fn uniformly_scaled(physical: Rect, scale: f64) -> Rect {
+    Rect {
+        left: (f64::from(physical.left) / scale).ceil() as i32,
+        top: (f64::from(physical.top) / scale).ceil() as i32,
+        right: (f64::from(physical.right) / scale).floor() as i32,
+        bottom: (f64::from(physical.bottom) / scale).floor() as i32,
+    }
+}
+
+/// Computes the rectangle to hand to a confinement backend working in `space`.
+pub fn clip_rect(monitor: &Monitor, space: CoordinateSpace) -> Rect {
+    match space {
+        CoordinateSpace::Physical => monitor.physical,
+        CoordinateSpace::Logical => monitor.logical,
+        CoordinateSpace::Uniform(scale) => uniformly_scaled(monitor.physical, scale),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edges(left: i32, top: i32, right: i32, bottom: i32) -> Rect {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    fn monitor(name: &str, physical: Rect, scale: f64) -> Monitor {
+        Monitor {
+            name: name.to_string(),
+            description: None,
+            id: Default::default(),
+            physical,
+            logical: physical_to_logical(physical, scale),
+            scale,
+        }
+    }
+
+    /// The layout from the README: a 100% monitor at a negative origin, a 150% primary and a 200% monitor.
+    fn readme_layout() -> Vec<Monitor> {
+        vec![
+            monitor(
+                "Optix G27C2",
+                Rect::from_origin_size(-1920, 593, 1920, 1080),
+                1.0,
+            ),
+            monitor("MAG274UPF", Rect::from_origin_size(0, 0, 3840, 2160), 1.5),
+            monitor(
+                "K222HQL",
+                Rect::from_origin_size(3840, 576, 1920, 1080),
+                2.0,
+            ),
+        ]
+    }
+
+    #[test]
+    fn physical_space_uses_device_pixels() {
+        let rects: Vec<Rect> = readme_layout()
+            .iter()
+            .map(|m| clip_rect(m, CoordinateSpace::Physical))
+            .collect();
+        assert_eq!(
+            rects,
+            vec![
+                edges(-1920, 593, 0, 1673),
+                edges(0, 0, 3840, 2160),
+                edges(3840, 576, 5760, 1656),
+            ]
+        );
+    }
+
+    #[test]
+    fn logical_space_keeps_origin_and_divides_size() {
+        let rects: Vec<Rect> = readme_layout()
+            .iter()
+            .map(|m| clip_rect(m, CoordinateSpace::Logical))
+            .collect();
+        assert_eq!(
+            rects,
+            vec![
+                edges(-1920, 593, 0, 1673),
+                edges(0, 0, 2560, 1440),
+                edges(3840, 576, 4800, 1116),
+            ]
+        );
+    }
+
+    #[test]
+    fn uniform_space_rounds_edges_inwards() {
+        let rects: Vec<Rect> = readme_layout()
+            .iter()
+            .map(|m| clip_rect(m, CoordinateSpace::Uniform(1.5)))
+            .collect();
+        assert_eq!(
+            rects,
+            vec![
+                // 593 / 1.5 = 395.33 and 1673 / 1.5 = 1115.33 both round towards the inside.
+                edges(-1280, 396, 0, 1115),
+                edges(0, 0, 2560, 1440),
+                edges(2560, 384, 3840, 1104),
+            ]
+        );
+    }
+
+    #[test]
+    fn uniform_space_at_200_percent_with_negative_origin() {
+        let physical = Rect::from_origin_size(-3841, -7, 1921, 1081);
+        assert_eq!(uniformly_scaled(physical, 2.0), edges(-1920, -3, -960, 537));
+    }
+
+    #[test]
+    fn logical_and_physical_round_trip() {
+        for m in readme_layout() {
+            assert_eq!(
+                logical_to_physical(m.logical, m.scale),
+                m.physical,
+                "{}",
+                m.name
+            );
+        }
+        // 125% does not divide 1366 evenly; the size is rounded to the nearest pixel.
+        let physical = Rect::from_origin_size(-1366, 0, 1366, 768);
+        let logical = physical_to_logical(physical, 1.25);
+        assert_eq!(logical, Rect::from_origin_size(-1366, 0, 1093, 614));
+    }
+}
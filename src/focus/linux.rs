@@ -0,0 +1,128 @@
+use crate::focus::FocusEvents;
+use eyre::Result;
+use std::path::Path;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::Atom;
+use x11rb::protocol::xproto::AtomEnum;
+use x11rb::protocol::xproto::ChangeWindowAttributesAux;
+use x11rb::protocol::xproto::ConnectionExt as _;
+use x11rb::protocol::xproto::EventMask;
+use x11rb::protocol::xproto::Window;
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+use x11rb::NONE;
+
+/// Placeholder focus event source for Wayland, which has no portable way to watch the foreground window.
+pub struct LinuxFocusEvents;
+
+impl FocusEvents for LinuxFocusEvents {
+    fn run(&mut self, _on_focus_change: Box<dyn FnMut(Option<String>) + Send>) -> Result<()> {
+        // No foreground notifications are available yet, so there is nothing to listen for.
+        Ok(())
+    }
+}
+
+/// Reports foreground changes on X11 by watching `_NET_ACTIVE_WINDOW` on the root window, which
+/// EWMH window managers keep up to date.
+pub struct X11FocusEvents {
+    conn: RustConnection,
+    root: Window,
+    active_window: Atom,
+    pid: Atom,
+}
+
+impl X11FocusEvents {
+    pub fn connect() -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+        let active_window = conn
+            .intern_atom(false, b"_NET_ACTIVE_WINDOW")?
+            .reply()?
+            .atom;
+        let pid = conn.intern_atom(false, b"_NET_WM_PID")?.reply()?.atom;
+        Ok(Self {
+            conn,
+            root,
+            active_window,
+            pid,
+        })
+    }
+
+    fn active_window(&self) -> Result<Option<Window>> {
+        let reply = self
+            .conn
+            .get_property(false, self.root, self.active_window, AtomEnum::WINDOW, 0, 1)?
+            .reply()?;
+        Ok(reply
+            .value32()
+            .and_then(|mut values| values.next())
+            .filter(|&window| window != NONE))
+    }
+
+    /// The executable name of the process that owns `window`, from its `_NET_WM_PID`.
+    fn app_name(&self, window: Window) -> Option<String> {
+        let reply = self
+            .conn
+            .get_property(false, window, self.pid, AtomEnum::CARDINAL, 0, 1)
+            .ok()?
+            .reply()
+            .ok()?;
+        let pid = reply.value32()?.next()?;
+        process_name(pid)
+    }
+}
+
+/// The executable name of process `pid`. `comm` is cut off at 15 bytes, so it is only used when
+/// the executable can't be read, e.g. for another user's process.
+fn process_name(pid: u32) -> Option<String> {
+    let exe = std::fs::read_link(format!("/proc/{}/exe", pid)).ok();
+    if let Some(name) = exe.as_deref().and_then(Path::file_name) {
+        return Some(name.to_string_lossy().into_owned());
+    }
+    let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(comm.trim_end().to_string())
+}
+
+impl FocusEvents for X11FocusEvents {
+    fn run(&mut self, mut on_focus_change: Box<dyn FnMut(Option<String>) + Send>) -> Result<()> {
+        self.conn
+            .change_window_attributes(
+                self.root,
+                &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+            )?
+            .check()?;
+        // Window managers rewrite the property on every click, so only report real changes.
+        let mut foreground = self.active_window()?;
+        loop {
+            if let Event::PropertyNotify(event) = self.conn.wait_for_event()? {
+                if event.atom != self.active_window {
+                    continue;
+                }
+                let window = self.active_window()?;
+                if window != foreground {
+                    foreground = window;
+                    on_focus_change(window.and_then(|window| self.app_name(window)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn process_names_are_not_cut_off() {
+        let exe = std::env::current_exe().unwrap();
+        let name = exe.file_name().unwrap().to_string_lossy();
+        // Test binaries are named after the crate plus a hash, well past the 15 bytes of `comm`.
+        assert!(name.len() > 15, "{}", name);
+        assert_eq!(process_name(std::process::id()).as_deref(), Some(&*name));
+    }
+
+    #[test]
+    fn missing_processes_have_no_name() {
+        assert_eq!(process_name(u32::MAX), None);
+    }
+}
@@ -0,0 +1,101 @@
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(windows)]
+mod win32;
+
+use crate::controller::Command;
+use crate::controller::ControllerHandle;
+use eyre::Result;
+use std::time::Duration;
+
+/// Delivers a notification whenever the foreground window changes.
+pub trait FocusEvents: Send {
+    /// Blocks the calling thread, invoking `on_focus_change` for every foreground change with the
+    /// executable name of the application that owns the new foreground window, if it can be told.
+    fn run(&mut self, on_focus_change: Box<dyn FnMut(Option<String>) + Send>) -> Result<()>;
+}
+
+/// Whether the executable `name` is `app`, ignoring case and a `.exe` extension on either.
+pub fn is_same_app(name: &str, app: &str) -> bool {
+    fn stem(name: &str) -> &str {
+        match name.len().checked_sub(4) {
+            Some(end) if name.is_char_boundary(end) && name[end..].eq_ignore_ascii_case(".exe") => {
+                &name[..end]
+            }
+            _ => name,
+        }
+    }
+    stem(name).eq_ignore_ascii_case(stem(app))
+}
+
+/// Returns the focus event source for the platform we were built for.
+#[cfg(windows)]
+pub fn default_backend() -> Result<Box<dyn FocusEvents>> {
+    Ok(Box::new(win32::Win32FocusEvents))
+}
+
+/// Returns the focus event source for the platform we were built for.
+#[cfg(target_os = "linux")]
+pub fn default_backend() -> Result<Box<dyn FocusEvents>> {
+    Ok(match crate::session::detect() {
+        crate::session::LinuxSession::X11 => Box::new(linux::X11FocusEvents::connect()?),
+        crate::session::LinuxSession::Wayland => Box::new(linux::LinuxFocusEvents),
+    })
+}
+
+/// Listens for foreground window changes and tells the controller which application is in front.
+/// With a `reapply_delay`, it also asks the controller to reapply the lock that long after the change.
+/// The event source runs on a new thread so that it keeps running.
+pub fn run_focus_hook(controller: ControllerHandle, reapply_delay: Option<Duration>) -> Result<()> {
+    std::thread::spawn(move || {
+        if let Err(e) = run_focus_hook_inner(controller, reapply_delay) {
+            eprintln!("Error in focus hook thread: {:?}", e);
+        }
+    });
+    Ok(())
+}
+
+pub fn run_focus_hook_inner(
+    controller: ControllerHandle,
+    reapply_delay: Option<Duration>,
+) -> Result<()> {
+    let mut backend = default_backend()?;
+    backend.run(Box::new(move |app| {
+        controller.send(Command::ForegroundApp(app));
+        match reapply_delay {
+            None => {}
+            Some(delay) if delay.is_zero() => controller.send(Command::FocusChanged),
+            Some(delay) => {
+                // Don't hold up the event source while we wait.
+                let controller = controller.clone();
+                std::thread::spawn(move || {
+                    std::thread::sleep(delay);
+                    controller.send(Command::FocusChanged);
+                });
+            }
+        }
+    }))
+}
+
+/// Spawns a thread that asks the controller to reapply the lock every `interval`,
+/// for games that reset it without a foreground change.
+pub fn run_periodic_reapply(controller: ControllerHandle, interval: Duration) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        controller.send(Command::Reapply);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apps_match_with_or_without_exe() {
+        assert!(is_same_app("obs64.exe", "OBS64.EXE"));
+        assert!(is_same_app("obs64.exe", "obs64"));
+        assert!(is_same_app("obs", "obs.exe"));
+        assert!(!is_same_app("obs64.exe", "obs"));
+        assert!(!is_same_app("exe", ".exe"));
+    }
+}
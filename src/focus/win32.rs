@@ -0,0 +1,112 @@
+use crate::focus::FocusEvents;
+use eyre::Result;
+use std::sync::Mutex;
+use windows::core::PWSTR;
+use windows::Win32::Foundation::CloseHandle;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::System::Threading::OpenProcess;
+use windows::Win32::System::Threading::QueryFullProcessImageNameW;
+use windows::Win32::System::Threading::PROCESS_NAME_WIN32;
+use windows::Win32::System::Threading::PROCESS_QUERY_LIMITED_INFORMATION;
+use windows::Win32::UI::Accessibility::SetWinEventHook;
+use windows::Win32::UI::Accessibility::UnhookWinEvent;
+use windows::Win32::UI::Accessibility::HWINEVENTHOOK;
+use windows::Win32::UI::WindowsAndMessaging::DispatchMessageW;
+use windows::Win32::UI::WindowsAndMessaging::GetMessageW;
+use windows::Win32::UI::WindowsAndMessaging::GetWindowThreadProcessId;
+use windows::Win32::UI::WindowsAndMessaging::TranslateMessage;
+use windows::Win32::UI::WindowsAndMessaging::EVENT_SYSTEM_FOREGROUND;
+use windows::Win32::UI::WindowsAndMessaging::MSG;
+use windows::Win32::UI::WindowsAndMessaging::WINEVENT_OUTOFCONTEXT;
+use windows::Win32::UI::WindowsAndMessaging::WINEVENT_SKIPOWNPROCESS;
+
+type FocusCallback = Box<dyn FnMut(Option<String>) + Send>;
+
+// The hook callback has no user data pointer, so the callback lives in a global.
+static FOCUS_CALLBACK: Mutex<Option<FocusCallback>> = Mutex::new(None);
+
+/// Reports foreground changes using a WinEvent hook on `EVENT_SYSTEM_FOREGROUND`.
+pub struct Win32FocusEvents;
+
+/// The executable name of the process that owns `hwnd`, e.g. `obs64.exe`.
+fn app_name(hwnd: HWND) -> Option<String> {
+    let mut pid = 0;
+    unsafe { GetWindowThreadProcessId(hwnd, Some(&mut pid)) };
+    if pid == 0 {
+        return None;
+    }
+    // Limited access is enough for the image name, and is granted even for elevated processes.
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }.ok()?;
+    let mut buffer = [0u16; 1024];
+    let mut length = buffer.len() as u32;
+    let queried = unsafe {
+        QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(buffer.as_mut_ptr()),
+            &mut length,
+        )
+    };
+    unsafe {
+        let _ = CloseHandle(process);
+    }
+    queried.ok()?;
+    let path = String::from_utf16_lossy(&buffer[..length as usize]);
+    path.rsplit('\\').next().map(str::to_string)
+}
+
+/// The WinEvent hook callback. When a foreground change is detected, we notify the callback.
+extern "system" fn win_event_proc(
+    _h_win_event_hook: HWINEVENTHOOK,
+    _event: u32,
+    hwnd: HWND,
+    _id_object: i32,
+    _id_child: i32,
+    _dw_event_thread: u32,
+    _dwms_event_time: u32,
+) {
+    if let Ok(mut guard) = FOCUS_CALLBACK.lock() {
+        if let Some(callback) = guard.as_mut() {
+            callback(app_name(hwnd));
+        }
+    }
+}
+
+impl FocusEvents for Win32FocusEvents {
+    fn run(&mut self, on_focus_change: Box<dyn FnMut(Option<String>) + Send>) -> Result<()> {
+        // Store our callback in the global.
+        *FOCUS_CALLBACK
+            .lock()
+            .map_err(|_| eyre::eyre!("Failed to set focus hook state"))? = Some(on_focus_change);
+
+        unsafe {
+            // Install the hook for EVENT_SYSTEM_FOREGROUND.
+            let hook = SetWinEventHook(
+                EVENT_SYSTEM_FOREGROUND,
+                EVENT_SYSTEM_FOREGROUND,
+                None,
+                Some(win_event_proc),
+                0,
+                0,
+                WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+            );
+            if hook.0.is_null() {
+                eyre::bail!("Failed to set WinEvent hook");
+            }
+
+            // Run a message loop to process hook events.
+            let mut msg = MSG::default();
+            loop {
+                // Block until a message is received.
+                if GetMessageW(&mut msg, HWND::default(), 0, 0).0 == 0 {
+                    break;
+                }
+                _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+            // Unhook when the message loop ends.
+            let _ = UnhookWinEvent(hook);
+        }
+        Ok(())
+    }
+}
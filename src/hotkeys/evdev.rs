@@ -0,0 +1,464 @@
+use crate::hotkeys::GlobalHotkeys;
+use crate::hotkeys::Hotkey;
+use crate::hotkeys::HotkeyEvent;
+use crate::key::Key;
+use crate::toggle_key::Chord;
+use crate::toggle_key::Modifiers;
+use evdev::Device;
+use evdev::EventType;
+use eyre::Result;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Sender;
+
+/// Input devices whose name starts with this were created by cursor-lock itself and are never read.
+pub const SYNTHETIC_DEVICE_PREFIX: &str = "cursor-lock";
+
+const INPUT_DIR: &str = "/dev/input";
+
+// Values of an evdev key event.
+const KEY_RELEASE: i32 = 0;
+const KEY_PRESS: i32 = 1;
+
+/// Reads keyboards, mice and gamepads straight from `/dev/input/event*`, so hotkeys work under any
+/// compositor and even on a bare console. Needs read access to the devices, usually through the
+/// `input` group. Unlike a grab, the key still reaches the focused application.
+pub struct EvdevHotkeys;
+
+enum Message {
+    Key { device: u64, code: u16, value: i32 },
+    Added(PathBuf),
+    Removed { device: u64, path: PathBuf },
+}
+
+/// Tracks which modifiers are held on which keyboard and matches presses against the bound chords.
+#[derive(Default)]
+struct ChordState {
+    held: HashMap<u64, HashSet<Key>>,
+    /// Keys that completed a chord and are still down, with the chord's position.
+    pressed: HashMap<(u64, Key), usize>,
+}
+
+impl ChordState {
+    /// Returns the chord this key event pressed or released, if any.
+    /// Auto-repeat events never complete a chord, so holding a key reports it once.
+    /// A chord is released with its key, whatever happened to the modifiers in between.
+    fn key_event(
+        &mut self,
+        chords: &[Chord],
+        device: u64,
+        key: Key,
+        value: i32,
+    ) -> Option<HotkeyEvent> {
+        if modifier_of(key).is_some() {
+            let held = self.held.entry(device).or_default();
+            match value {
+                KEY_PRESS => held.insert(key),
+                KEY_RELEASE => held.remove(&key),
+                _ => false,
+            };
+            return None;
+        }
+        match value {
+            KEY_PRESS => {}
+            KEY_RELEASE => {
+                return self
+                    .pressed
+                    .remove(&(device, key))
+                    .map(HotkeyEvent::Released)
+            }
+            _ => return None,
+        }
+        let mut modifiers = Modifiers::NONE;
+        for &held in self.held.values().flatten() {
+            modifiers |= modifier_of(held).unwrap_or(Modifiers::NONE);
+        }
+        let index = chords
+            .iter()
+            .position(|chord| chord.key == key && chord.modifiers == modifiers)?;
+        self.pressed.insert((device, key), index);
+        Some(HotkeyEvent::Pressed(index))
+    }
+
+    /// Forgets the keys held on an unplugged keyboard, so they don't stay held forever.
+    /// Returns the chords that were down on it, which count as released.
+    fn remove_device(&mut self, device: u64) -> Vec<HotkeyEvent> {
+        self.held.remove(&device);
+        let mut released = Vec::new();
+        self.pressed.retain(|&(pressed_on, _), &mut index| {
+            if pressed_on == device {
+                released.push(HotkeyEvent::Released(index));
+            }
+            pressed_on != device
+        });
+        released
+    }
+}
+
+fn modifier_of(key: Key) -> Option<Modifiers> {
+    match key {
+        Key::LeftCtrl | Key::RightCtrl => Some(Modifiers::CTRL),
+        Key::LeftAlt | Key::RightAlt => Some(Modifiers::ALT),
+        Key::LeftShift | Key::RightShift => Some(Modifiers::SHIFT),
+        Key::LeftSuper | Key::RightSuper => Some(Modifiers::SUPER),
+        _ => None,
+    }
+}
+
+/// Opens `path` if it is a device that can produce one of `codes` and isn't one of ours.
+fn open_device(path: &Path, codes: &[u16]) -> io::Result<Option<Device>> {
+    let device = Device::open(path)?;
+    if device
+        .name()
+        .is_some_and(|name| name.starts_with(SYNTHETIC_DEVICE_PREFIX))
+    {
+        return Ok(None);
+    }
+    let has_keys = device.supported_keys().is_some_and(|keys| {
+        codes
+            .iter()
+            .any(|&code| keys.contains(evdev::KeyCode(code)))
+    });
+    Ok(has_keys.then_some(device))
+}
+
+/// Forwards the key events of `device` until it goes away.
+fn read_device(mut device: Device, id: u64, path: PathBuf, sender: Sender<Message>) {
+    std::thread::spawn(move || {
+        loop {
+            let events = match device.fetch_events() {
+                Ok(events) => events,
+                Err(_) => break,
+            };
+            for event in events {
+                if event.event_type() == EventType::KEY {
+                    let message = Message::Key {
+                        device: id,
+                        code: event.code(),
+                        value: event.value(),
+                    };
+                    if sender.send(message).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+        let _ = sender.send(Message::Removed { device: id, path });
+    });
+}
+
+/// Reports `event*` nodes appearing in `/dev/input`. Nodes are also reported when their permissions
+/// change, because udev only grants access after creating them.
+fn watch_for_devices(sender: Sender<Message>) -> Result<()> {
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    let dir = CString::new(INPUT_DIR)?;
+    if unsafe { libc::inotify_add_watch(fd, dir.as_ptr(), libc::IN_CREATE | libc::IN_ATTRIB) } < 0 {
+        let error = io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return Err(error.into());
+    }
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = unsafe { libc::read(fd, buffer.as_mut_ptr().cast(), buffer.len()) };
+            if read <= 0 {
+                break;
+            }
+            let mut offset = 0;
+            let header = std::mem::size_of::<libc::inotify_event>();
+            while offset + header <= read as usize {
+                let event: libc::inotify_event =
+                    unsafe { std::ptr::read_unaligned(buffer[offset..].as_ptr().cast()) };
+                let name = &buffer[offset + header..offset + header + event.len as usize];
+                let name = name.split(|&b| b == 0).next().unwrap_or_default();
+                offset += header + event.len as usize;
+                if name.starts_with(b"event") {
+                    let path = Path::new(INPUT_DIR).join(std::ffi::OsStr::from_bytes(name));
+                    if sender.send(Message::Added(path)).is_err() {
+                        unsafe { libc::close(fd) };
+                        return;
+                    }
+                }
+            }
+        }
+        unsafe { libc::close(fd) };
+    });
+    Ok(())
+}
+
+impl GlobalHotkeys for EvdevHotkeys {
+    fn run(
+        &mut self,
+        keys: &[Hotkey],
+        mut on_hotkey: Box<dyn FnMut(HotkeyEvent) + Send>,
+    ) -> Result<()> {
+        // Reading devices takes nothing away from other applications, so fallbacks are never needed.
+        let keys: Vec<Chord> = keys.iter().map(|hotkey| hotkey.chord).collect();
+        let keys = &keys[..];
+        // Devices with the bound keys, plus keyboards for the modifiers of chords on other devices,
+        // e.g. the Ctrl of `Ctrl+MouseBack`.
+        let mut codes: Vec<u16> = keys.iter().map(|chord| chord.key.to_evdev()).collect();
+        for (modifier, key) in [
+            (Modifiers::CTRL, Key::LeftCtrl),
+            (Modifiers::ALT, Key::LeftAlt),
+            (Modifiers::SHIFT, Key::LeftShift),
+            (Modifiers::SUPER, Key::LeftSuper),
+        ] {
+            if keys.iter().any(|chord| chord.modifiers.contains(modifier)) {
+                codes.push(key.to_evdev());
+            }
+        }
+        let (sender, receiver) = mpsc::channel();
+        watch_for_devices(sender.clone())?;
+
+        let mut opened = HashSet::new();
+        let mut next_id = 0;
+        let mut open = |path: PathBuf, opened: &mut HashSet<PathBuf>| -> io::Result<()> {
+            if opened.contains(&path) {
+                return Ok(());
+            }
+            if let Some(device) = open_device(&path, &codes)? {
+                opened.insert(path.clone());
+                read_device(device, next_id, path, sender.clone());
+                next_id += 1;
+            }
+            Ok(())
+        };
+
+        let mut denied = false;
+        for entry in std::fs::read_dir(INPUT_DIR)? {
+            let path = entry?.path();
+            if !path
+                .file_name()
+                .is_some_and(|name| name.as_bytes().starts_with(b"event"))
+            {
+                continue;
+            }
+            match open(path, &mut opened) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => denied = true,
+                Err(e) => eprintln!("Skipping an input device: {}", e),
+            }
+        }
+        if opened.is_empty() {
+            if denied {
+                eyre::bail!(
+                    "No permission to read input devices in {}; add yourself to the `input` group and log in again",
+                    INPUT_DIR
+                );
+            }
+            println!("No device with the bound keys found yet; hotkeys will work once one is plugged in.");
+        }
+
+        let mut state = ChordState::default();
+        for message in receiver {
+            match message {
+                Message::Key {
+                    device,
+                    code,
+                    value,
+                } => {
+                    let Some(key) = Key::from_evdev(code) else {
+                        continue;
+                    };
+                    if let Some(event) = state.key_event(keys, device, key, value) {
+                        on_hotkey(event);
+                    }
+                }
+                Message::Added(path) => {
+                    // Permission errors are expected until udev has set the node up.
+                    let _ = open(path, &mut opened);
+                }
+                Message::Removed { device, path } => {
+                    opened.remove(&path);
+                    for event in state.remove_device(device) {
+                        on_hotkey(event);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hotkeys::Role;
+    use evdev::uinput::VirtualDevice;
+    use evdev::AttributeSet;
+    use evdev::InputEvent;
+    use evdev::KeyCode;
+    use std::time::Duration;
+
+    const CTRL_F9: Chord = Chord {
+        modifiers: Modifiers::CTRL,
+        key: Key::F9,
+    };
+
+    #[test]
+    fn chords_need_exactly_their_modifiers() {
+        let mut state = ChordState::default();
+        let chords = [CTRL_F9];
+        assert_eq!(state.key_event(&chords, 0, Key::F9, KEY_PRESS), None);
+        assert_eq!(state.key_event(&chords, 0, Key::F9, KEY_RELEASE), None);
+
+        state.key_event(&chords, 0, Key::LeftCtrl, KEY_PRESS);
+        assert_eq!(
+            state.key_event(&chords, 0, Key::F9, KEY_PRESS),
+            Some(HotkeyEvent::Pressed(0))
+        );
+        // Auto-repeat doesn't fire again.
+        assert_eq!(state.key_event(&chords, 0, Key::F9, 2), None);
+        assert_eq!(
+            state.key_event(&chords, 0, Key::F9, KEY_RELEASE),
+            Some(HotkeyEvent::Released(0))
+        );
+
+        state.key_event(&chords, 0, Key::LeftShift, KEY_PRESS);
+        assert_eq!(state.key_event(&chords, 0, Key::F9, KEY_PRESS), None);
+    }
+
+    #[test]
+    fn release_follows_the_key_not_the_modifiers() {
+        let mut state = ChordState::default();
+        let chords = [CTRL_F9];
+        state.key_event(&chords, 0, Key::LeftCtrl, KEY_PRESS);
+        state.key_event(&chords, 0, Key::F9, KEY_PRESS);
+        assert_eq!(
+            state.key_event(&chords, 0, Key::LeftCtrl, KEY_RELEASE),
+            None
+        );
+        assert_eq!(
+            state.key_event(&chords, 0, Key::F9, KEY_RELEASE),
+            Some(HotkeyEvent::Released(0))
+        );
+    }
+
+    #[test]
+    fn modifiers_combine_across_keyboards_and_clear_on_unplug() {
+        let mut state = ChordState::default();
+        let chords = [CTRL_F9];
+        state.key_event(&chords, 1, Key::RightCtrl, KEY_PRESS);
+        assert_eq!(
+            state.key_event(&chords, 0, Key::F9, KEY_PRESS),
+            Some(HotkeyEvent::Pressed(0))
+        );
+        assert_eq!(state.remove_device(0), [HotkeyEvent::Released(0)]);
+
+        assert_eq!(state.remove_device(1), []);
+        assert_eq!(state.key_event(&chords, 0, Key::F9, KEY_PRESS), None);
+    }
+
+    fn virtual_device(name: &str, keys: &[Key]) -> VirtualDevice {
+        let mut codes = AttributeSet::<KeyCode>::new();
+        for key in keys {
+            codes.insert(KeyCode(key.to_evdev()));
+        }
+        VirtualDevice::builder()
+            .unwrap()
+            .name(name)
+            .with_keys(&codes)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn emit(device: &mut VirtualDevice, key: Key, value: i32) {
+        let event = InputEvent::new(EventType::KEY.0, key.to_evdev(), value);
+        device.emit(&[event]).unwrap();
+    }
+
+    fn type_chord(keyboard: &mut VirtualDevice) {
+        emit(keyboard, Key::LeftCtrl, KEY_PRESS);
+        emit(keyboard, Key::F9, KEY_PRESS);
+        emit(keyboard, Key::F9, KEY_RELEASE);
+        emit(keyboard, Key::LeftCtrl, KEY_RELEASE);
+    }
+
+    /// Runs the backend on its own thread, forwarding what it reports.
+    fn listen(keys: Vec<Chord>) -> mpsc::Receiver<HotkeyEvent> {
+        let keys: Vec<Hotkey> = keys
+            .into_iter()
+            .map(|chord| Hotkey {
+                chord,
+                fallback: None,
+                role: Role::Always,
+            })
+            .collect();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            EvdevHotkeys
+                .run(&keys, Box::new(move |event| sender.send(event).unwrap()))
+                .unwrap();
+        });
+        std::thread::sleep(Duration::from_millis(500));
+        receiver
+    }
+
+    #[test]
+    #[ignore = "needs write access to /dev/uinput and read access to /dev/input"]
+    fn hotplugged_keyboards_are_read_and_synthetic_ones_ignored() {
+        let receiver = listen(vec![CTRL_F9]);
+        let keys = [Key::F9, Key::LeftCtrl];
+        let mut ours = virtual_device(&format!("{} test keyboard", SYNTHETIC_DEVICE_PREFIX), &keys);
+        let mut theirs = virtual_device("virtual test keyboard", &keys);
+        // Let udev and the watcher pick both devices up.
+        std::thread::sleep(Duration::from_secs(1));
+
+        type_chord(&mut ours);
+        assert!(receiver.recv_timeout(Duration::from_millis(500)).is_err());
+        type_chord(&mut theirs);
+        let timeout = Duration::from_secs(5);
+        assert_eq!(receiver.recv_timeout(timeout), Ok(HotkeyEvent::Pressed(0)));
+        assert_eq!(receiver.recv_timeout(timeout), Ok(HotkeyEvent::Released(0)));
+    }
+
+    #[test]
+    #[ignore = "needs write access to /dev/uinput and read access to /dev/input"]
+    fn mouse_and_gamepad_buttons_are_read() {
+        let ctrl_back = Chord {
+            modifiers: Modifiers::CTRL,
+            key: Key::MouseBack,
+        };
+        let pad_south = Chord {
+            modifiers: Modifiers::NONE,
+            key: Key::PadSouth,
+        };
+        let receiver = listen(vec![ctrl_back, pad_south]);
+        let mut keyboard = virtual_device("virtual test keyboard", &[Key::LeftCtrl]);
+        let mut mouse = virtual_device("virtual test mouse", &[Key::MouseBack]);
+        let mut gamepad = virtual_device("virtual test gamepad", &[Key::PadSouth]);
+        std::thread::sleep(Duration::from_secs(1));
+
+        // The modifier comes from another device than the button. Each device has its own reader
+        // thread, so give one time to deliver before using the other.
+        let settle = || std::thread::sleep(Duration::from_millis(100));
+        emit(&mut keyboard, Key::LeftCtrl, KEY_PRESS);
+        settle();
+        emit(&mut mouse, Key::MouseBack, KEY_PRESS);
+        emit(&mut mouse, Key::MouseBack, KEY_RELEASE);
+        settle();
+        emit(&mut keyboard, Key::LeftCtrl, KEY_RELEASE);
+        emit(&mut gamepad, Key::PadSouth, KEY_PRESS);
+        emit(&mut gamepad, Key::PadSouth, KEY_RELEASE);
+        let timeout = Duration::from_secs(5);
+        for expected in [
+            HotkeyEvent::Pressed(0),
+            HotkeyEvent::Released(0),
+            HotkeyEvent::Pressed(1),
+            HotkeyEvent::Released(1),
+        ] {
+            assert_eq!(receiver.recv_timeout(timeout), Ok(expected));
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use crate::controller::Command;
+use crate::controller::ControllerHandle;
+use serde::Deserialize;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+use std::time::Instant;
+
+/// What a gesture on the toggle key asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GestureAction {
+    Toggle,
+    NextMonitor,
+    PreviousMonitor,
+    /// Release the cursor and quit.
+    Exit,
+    /// Leave the gesture unbound. Taps then fire without waiting to see if a second one follows.
+    Nothing,
+}
+
+impl GestureAction {
+    fn command(self) -> Option<Command> {
+        match self {
+            GestureAction::Toggle => Some(Command::Toggle),
+            GestureAction::NextMonitor => Some(Command::NextMonitor),
+            GestureAction::PreviousMonitor => Some(Command::PreviousMonitor),
+            GestureAction::Exit => Some(Command::Shutdown),
+            GestureAction::Nothing => None,
+        }
+    }
+}
+
+/// The actions bound to each gesture on one key, and the timings that tell the gestures apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gestures {
+    pub tap: GestureAction,
+    pub double_tap: GestureAction,
+    pub long_press: GestureAction,
+    /// How soon after releasing a tap the second press must come to make a double tap.
+    pub double_tap_window: Duration,
+    /// How long the key must be held to make a long press.
+    pub long_press_threshold: Duration,
+}
+
+impl Gestures {
+    fn action(&self, gesture: Gesture) -> GestureAction {
+        match gesture {
+            Gesture::Tap => self.tap,
+            Gesture::DoubleTap => self.double_tap,
+            Gesture::LongPress => self.long_press,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Gesture {
+    Tap,
+    DoubleTap,
+    LongPress,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    /// The key is down; `second` if a tap came just before.
+    Down {
+        since: Instant,
+        second: bool,
+    },
+    /// A tap was released and may still become a double tap.
+    Released {
+        at: Instant,
+    },
+    /// The long press has fired; waiting for the key to come up.
+    LongPressed,
+}
+
+/// Turns timestamped presses and releases of one key into gestures.
+/// Time only moves through the instants passed in, so it can be driven by a fake clock.
+struct Recognizer {
+    /// `None` when double taps are unbound, so taps don't wait for one.
+    double_tap_window: Option<Duration>,
+    /// `None` when long presses are unbound, so holding the key is just a slow tap.
+    long_press_threshold: Option<Duration>,
+    state: State,
+}
+
+impl Recognizer {
+    fn new(gestures: &Gestures) -> Self {
+        let bound = |action, duration| (action != GestureAction::Nothing).then_some(duration);
+        Self {
+            double_tap_window: bound(gestures.double_tap, gestures.double_tap_window),
+            long_press_threshold: bound(gestures.long_press, gestures.long_press_threshold),
+            state: State::Idle,
+        }
+    }
+
+    fn press(&mut self, at: Instant) -> Option<Gesture> {
+        // A tap still waiting for its window to close is settled first.
+        let pending = self.poll(at);
+        let second = matches!(self.state, State::Released { .. });
+        self.state = State::Down { since: at, second };
+        pending
+    }
+
+    fn release(&mut self, at: Instant) -> Option<Gesture> {
+        if let Some(gesture) = self.poll(at) {
+            // The long press fired late because nothing polled in time.
+            self.state = State::Idle;
+            return Some(gesture);
+        }
+        let State::Down { second, .. } = self.state else {
+            self.state = State::Idle;
+            return None;
+        };
+        if second {
+            self.state = State::Idle;
+            Some(Gesture::DoubleTap)
+        } else if self.double_tap_window.is_some() {
+            self.state = State::Released { at };
+            None
+        } else {
+            self.state = State::Idle;
+            Some(Gesture::Tap)
+        }
+    }
+
+    /// Reports the gesture that `now` completes by passing a deadline, if any.
+    fn poll(&mut self, now: Instant) -> Option<Gesture> {
+        match self.deadline() {
+            Some(deadline) if now >= deadline => {}
+            _ => return None,
+        }
+        match self.state {
+            State::Down { .. } => {
+                self.state = State::LongPressed;
+                Some(Gesture::LongPress)
+            }
+            State::Released { .. } => {
+                self.state = State::Idle;
+                Some(Gesture::Tap)
+            }
+            State::Idle | State::LongPressed => None,
+        }
+    }
+
+    /// When [`Recognizer::poll`] should next be called, if something is waiting on time.
+    fn deadline(&self) -> Option<Instant> {
+        match self.state {
+            State::Down { since, .. } => Some(since + self.long_press_threshold?),
+            State::Released { at } => Some(at + self.double_tap_window?),
+            State::Idle | State::LongPressed => None,
+        }
+    }
+}
+
+/// Spawns a thread that recognizes gestures in `events` (pressed or not, and when) and sends the
+/// bound actions to the controller.
+pub fn spawn_recognizer(
+    controller: ControllerHandle,
+    gestures: Gestures,
+    events: Receiver<(bool, Instant)>,
+) {
+    std::thread::spawn(move || {
+        let mut recognizer = Recognizer::new(&gestures);
+        loop {
+            let gesture = match recognizer.deadline() {
+                Some(deadline) => {
+                    match events.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok((true, at)) => recognizer.press(at),
+                        Ok((false, at)) => recognizer.release(at),
+                        Err(RecvTimeoutError::Timeout) => recognizer.poll(Instant::now()),
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                None => match events.recv() {
+                    Ok((true, at)) => recognizer.press(at),
+                    Ok((false, at)) => recognizer.release(at),
+                    Err(_) => return,
+                },
+            };
+            let Some(gesture) = gesture else {
+                continue;
+            };
+            let action = gestures.action(gesture);
+            if action == GestureAction::Exit {
+                println!("Gesture {:?}: releasing the cursor and exiting.", gesture);
+            }
+            if let Some(command) = action.command() {
+                controller.send(command);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A clock that only moves when told to.
+    struct FakeClock(Instant);
+
+    impl FakeClock {
+        fn advance(&mut self, ms: u64) -> Instant {
+            self.0 += Duration::from_millis(ms);
+            self.0
+        }
+    }
+
+    fn gestures() -> Gestures {
+        Gestures {
+            tap: GestureAction::Toggle,
+            double_tap: GestureAction::NextMonitor,
+            long_press: GestureAction::Exit,
+            double_tap_window: Duration::from_millis(300),
+            long_press_threshold: Duration::from_millis(800),
+        }
+    }
+
+    #[test]
+    fn tap_fires_once_the_double_tap_window_closes() {
+        let mut clock = FakeClock(Instant::now());
+        let mut recognizer = Recognizer::new(&gestures());
+        assert_eq!(recognizer.press(clock.advance(0)), None);
+        assert_eq!(recognizer.release(clock.advance(100)), None);
+        assert_eq!(recognizer.poll(clock.advance(299)), None);
+        assert_eq!(recognizer.poll(clock.advance(1)), Some(Gesture::Tap));
+        assert_eq!(recognizer.deadline(), None);
+    }
+
+    #[test]
+    fn second_tap_in_the_window_is_a_double_tap() {
+        let mut clock = FakeClock(Instant::now());
+        let mut recognizer = Recognizer::new(&gestures());
+        recognizer.press(clock.advance(0));
+        recognizer.release(clock.advance(100));
+        assert_eq!(recognizer.press(clock.advance(200)), None);
+        assert_eq!(
+            recognizer.release(clock.advance(100)),
+            Some(Gesture::DoubleTap)
+        );
+        assert_eq!(recognizer.poll(clock.advance(1000)), None);
+    }
+
+    #[test]
+    fn late_second_tap_is_two_taps() {
+        let mut clock = FakeClock(Instant::now());
+        let mut recognizer = Recognizer::new(&gestures());
+        recognizer.press(clock.advance(0));
+        recognizer.release(clock.advance(100));
+        // Nothing polled in time, so the next press settles the first tap.
+        assert_eq!(recognizer.press(clock.advance(400)), Some(Gesture::Tap));
+        assert_eq!(recognizer.release(clock.advance(100)), None);
+        assert_eq!(recognizer.poll(clock.advance(300)), Some(Gesture::Tap));
+    }
+
+    #[test]
+    fn holding_past_the_threshold_is_a_long_press() {
+        let mut clock = FakeClock(Instant::now());
+        let mut recognizer = Recognizer::new(&gestures());
+        recognizer.press(clock.advance(0));
+        assert_eq!(recognizer.poll(clock.advance(799)), None);
+        assert_eq!(recognizer.poll(clock.advance(1)), Some(Gesture::LongPress));
+        assert_eq!(recognizer.release(clock.advance(500)), None);
+        assert_eq!(recognizer.poll(clock.advance(1000)), None);
+
+        // Released after the threshold without a poll in between.
+        recognizer.press(clock.advance(0));
+        assert_eq!(
+            recognizer.release(clock.advance(900)),
+            Some(Gesture::LongPress)
+        );
+    }
+
+    #[test]
+    fn unbound_gestures_dont_delay_the_others() {
+        let mut clock = FakeClock(Instant::now());
+        let mut recognizer = Recognizer::new(&Gestures {
+            double_tap: GestureAction::Nothing,
+            long_press: GestureAction::Nothing,
+            ..gestures()
+        });
+        recognizer.press(clock.advance(0));
+        assert_eq!(recognizer.deadline(), None);
+        assert_eq!(recognizer.release(clock.advance(5000)), Some(Gesture::Tap));
+    }
+}
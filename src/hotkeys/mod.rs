@@ -0,0 +1,366 @@
+#[cfg(target_os = "linux")]
+mod evdev;
+pub mod gesture;
+#[cfg_attr(not(any(windows, test)), allow(dead_code))]
+mod registration;
+pub mod sequence;
+#[cfg(windows)]
+mod win32;
+#[cfg(target_os = "linux")]
+mod x11;
+
+use crate::controller::Command;
+use crate::controller::ControllerHandle;
+use crate::key::KeyKind;
+use crate::toggle_key::Chord;
+use eyre::Result;
+use gesture::Gestures;
+use sequence::Step;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+/// What pressing a bound key asks the controller to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyAction {
+    Toggle,
+    /// Release the cursor while the key is held.
+    HoldToUnlock,
+    /// Lock the cursor while the key is held.
+    HoldToLock,
+    /// Tell taps, double taps and long presses apart and run the action bound to each.
+    Gestures(Gestures),
+    NextMonitor,
+    PreviousMonitor,
+    /// Lock to the monitor at this position in `cursor-lock list`, counting from 0.
+    LockToMonitor(usize),
+    /// Arm the keys that follow it for this long.
+    Leader(Duration),
+    /// Only does something right after the leader.
+    AfterLeader(Step),
+    ToggleMute,
+}
+
+impl HotkeyAction {
+    /// The command for the key going down (`pressed`) or up, if the action cares about that edge.
+    fn command(self, pressed: bool) -> Option<Command> {
+        match (self, pressed) {
+            (HotkeyAction::HoldToUnlock, true) => Some(Command::Hold(false)),
+            (HotkeyAction::HoldToLock, true) => Some(Command::Hold(true)),
+            (HotkeyAction::HoldToUnlock | HotkeyAction::HoldToLock, false) => {
+                Some(Command::EndHold)
+            }
+            (_, false)
+            | (
+                HotkeyAction::Gestures(_) | HotkeyAction::Leader(_) | HotkeyAction::AfterLeader(_),
+                _,
+            ) => None,
+            (HotkeyAction::Toggle, true) => Some(Command::Toggle),
+            (HotkeyAction::NextMonitor, true) => Some(Command::NextMonitor),
+            (HotkeyAction::PreviousMonitor, true) => Some(Command::PreviousMonitor),
+            (HotkeyAction::LockToMonitor(index), true) => Some(Command::LockToMonitor(index)),
+            (HotkeyAction::ToggleMute, true) => Some(Command::ToggleMute),
+        }
+    }
+}
+
+/// Names the key by what it does, for messages about it.
+impl fmt::Display for HotkeyAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HotkeyAction::Toggle
+            | HotkeyAction::HoldToUnlock
+            | HotkeyAction::HoldToLock
+            | HotkeyAction::Gestures(_) => write!(f, "toggle key"),
+            HotkeyAction::NextMonitor => write!(f, "next-monitor key"),
+            HotkeyAction::PreviousMonitor => write!(f, "previous-monitor key"),
+            HotkeyAction::LockToMonitor(index) => write!(f, "key for monitor {}", index + 1),
+            HotkeyAction::Leader(_) => write!(f, "leader key"),
+            HotkeyAction::AfterLeader(step) => write!(f, "leader key's {} key", step),
+            HotkeyAction::ToggleMute => write!(f, "mute key"),
+        }
+    }
+}
+
+/// Something that happened to a hotkey, by its position in the registered keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotkeyEvent {
+    Pressed(usize),
+    Released(usize),
+    /// The platform refused `chord`. `retrying` if its owner may let go of it, in which case it is
+    /// registered as soon as that happens.
+    Unavailable {
+        index: usize,
+        chord: Chord,
+        reason: String,
+        /// Chords that were free when this was reported.
+        suggestions: Vec<Chord>,
+        retrying: bool,
+    },
+    /// `chord` is registered after all, either on retry or as the fallback `instead_of` another.
+    Registered {
+        index: usize,
+        chord: Chord,
+        instead_of: Option<Chord>,
+    },
+}
+
+impl HotkeyEvent {
+    /// The same event for the hotkey at position `map(index)`.
+    fn with_index(self, map: impl Fn(usize) -> usize) -> HotkeyEvent {
+        match self {
+            HotkeyEvent::Pressed(index) => HotkeyEvent::Pressed(map(index)),
+            HotkeyEvent::Released(index) => HotkeyEvent::Released(map(index)),
+            HotkeyEvent::Unavailable {
+                index,
+                chord,
+                reason,
+                suggestions,
+                retrying,
+            } => HotkeyEvent::Unavailable {
+                index: map(index),
+                chord,
+                reason,
+                suggestions,
+                retrying,
+            },
+            HotkeyEvent::Registered {
+                index,
+                chord,
+                instead_of,
+            } => HotkeyEvent::Registered {
+                index: map(index),
+                chord,
+                instead_of,
+            },
+        }
+    }
+}
+
+/// A chord and the action it triggers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+    pub key: Chord,
+    /// Registered instead while another application holds `key`.
+    pub fallback: Option<Chord>,
+    pub action: HotkeyAction,
+}
+
+/// When a backend needs to register a hotkey.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Always,
+    /// Always, and pressing it registers the followers for this long.
+    Leader(Duration),
+    /// Only while a leader is armed, so the key stays free for other applications otherwise.
+    /// Backends that see every key without registering anything may report it at any time.
+    Follower,
+}
+
+/// A chord for a backend to register, and the one to register while the platform refuses it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hotkey {
+    pub chord: Chord,
+    pub fallback: Option<Chord>,
+    pub role: Role,
+}
+
+/// Registers system-wide hotkeys and reports when they are pressed and released.
+pub trait GlobalHotkeys: Send {
+    /// Blocks the calling thread, invoking `on_hotkey` whenever a chord in `keys` is pressed and
+    /// again when its key is released. Holding a chord down reports one press, not one per key repeat.
+    /// A chord the platform refuses is reported as [`HotkeyEvent::Unavailable`] without stopping
+    /// the others; `Err` is only for the backend failing as a whole.
+    fn run(&mut self, keys: &[Hotkey], on_hotkey: Box<dyn FnMut(HotkeyEvent) + Send>)
+        -> Result<()>;
+}
+
+/// Returns the backend that listens to keys of this kind on the platform we were built for.
+#[cfg(windows)]
+pub fn default_backend(kind: KeyKind) -> Result<Box<dyn GlobalHotkeys>> {
+    Ok(match kind {
+        KeyKind::Keyboard => Box::new(win32::Win32Hotkeys),
+        KeyKind::Mouse => Box::new(win32::Win32MouseButtons),
+        KeyKind::Gamepad => Box::new(win32::XInputButtons),
+    })
+}
+
+/// Returns the backend that listens to keys of this kind on the platform we were built for.
+/// Wayland compositors don't allow global grabs, so there (and without any display, e.g. on a VT)
+/// devices are read directly through evdev. Gamepads are always read through evdev.
+#[cfg(target_os = "linux")]
+pub fn default_backend(kind: KeyKind) -> Result<Box<dyn GlobalHotkeys>> {
+    use crate::session::LinuxSession;
+
+    fn or_evdev<T: GlobalHotkeys + 'static>(backend: Result<T>) -> Box<dyn GlobalHotkeys> {
+        match backend {
+            Ok(backend) => Box::new(backend),
+            Err(e) => {
+                eprintln!(
+                    "Can't listen through X11 ({}); reading input devices through evdev instead",
+                    e
+                );
+                Box::new(evdev::EvdevHotkeys)
+            }
+        }
+    }
+
+    Ok(match (crate::session::detect(), kind) {
+        (LinuxSession::X11, KeyKind::Keyboard) => or_evdev(x11::X11Hotkeys::connect()),
+        (LinuxSession::X11, KeyKind::Mouse) => or_evdev(x11::X11Buttons::connect()),
+        _ => Box::new(evdev::EvdevHotkeys),
+    })
+}
+
+/// Spawns threads that register the bound keys as global hotkeys and listen for them, one per kind
+/// of device. Presses (and, for momentary actions, releases) send the binding's action to the controller.
+pub fn run_hotkey_listener(controller: ControllerHandle, bindings: Vec<Binding>) -> Result<()> {
+    // Catch clashes before the platform reports them as a vague registration failure.
+    let chords = || {
+        bindings.iter().flat_map(|binding| {
+            [Some(binding.key), binding.fallback]
+                .into_iter()
+                .flatten()
+                .map(move |chord| (chord, binding.action))
+        })
+    };
+    for (i, (chord, action)) in chords().enumerate() {
+        if let Some((_, other)) = chords().take(i).find(|(other, _)| *other == chord) {
+            eyre::bail!(
+                "{} is bound to both the {} and the {}",
+                chord,
+                other,
+                action
+            );
+        }
+    }
+
+    // Leaders and their followers all go to the same backend, which has to see the leader to
+    // register the followers.
+    let leader = bindings.iter().find_map(|binding| match binding.action {
+        HotkeyAction::Leader(timeout) => Some((binding.key, timeout)),
+        _ => None,
+    });
+    if let Some((chord, _)) = leader {
+        if chord.key.kind() != KeyKind::Keyboard {
+            eyre::bail!("The leader key must be on the keyboard, not {}", chord);
+        }
+    }
+
+    // Keys with gestures feed their own recognizer instead of sending commands directly.
+    let mut recognizers = HashMap::new();
+    for (index, binding) in bindings.iter().enumerate() {
+        if let HotkeyAction::Gestures(gestures) = binding.action {
+            let (sender, receiver) = mpsc::channel();
+            gesture::spawn_recognizer(controller.clone(), gestures, receiver);
+            recognizers.insert(index, sender);
+        }
+    }
+    // The leader and the keys after it feed the sequencer.
+    let sequencer = leader.map(|(_, timeout)| {
+        let (sender, receiver) = mpsc::channel();
+        sequence::spawn_sequencer(controller.clone(), timeout, receiver);
+        sender
+    });
+    let actions: Vec<HotkeyAction> = bindings.iter().map(|binding| binding.action).collect();
+    let dispatch = Arc::new(move |event| {
+        let (index, pressed) = match event {
+            HotkeyEvent::Pressed(index) => (index, true),
+            HotkeyEvent::Released(index) => (index, false),
+            HotkeyEvent::Unavailable {
+                index,
+                chord,
+                reason,
+                suggestions,
+                retrying,
+            } => {
+                controller.send(Command::HotkeyUnavailable {
+                    hotkey: format!("the {} {}", actions[index], chord),
+                    reason,
+                    suggestions,
+                    retrying,
+                });
+                return;
+            }
+            HotkeyEvent::Registered {
+                index,
+                chord,
+                instead_of,
+            } => {
+                controller.send(Command::HotkeyRegistered {
+                    hotkey: format!("the {} {}", actions[index], chord),
+                    instead_of,
+                });
+                return;
+            }
+        };
+        if let Some(recognizer) = recognizers.get(&index) {
+            let _ = recognizer.send((pressed, Instant::now()));
+        } else if let (HotkeyAction::Leader(_), Some(sequencer), true) =
+            (actions[index], &sequencer, pressed)
+        {
+            let _ = sequencer.send((None, Instant::now()));
+        } else if let (HotkeyAction::AfterLeader(step), Some(sequencer), true) =
+            (actions[index], &sequencer, pressed)
+        {
+            let _ = sequencer.send((Some(step), Instant::now()));
+        } else if let Some(command) = actions[index].command(pressed) {
+            controller.send(command);
+        }
+    });
+
+    for kind in [KeyKind::Keyboard, KeyKind::Mouse, KeyKind::Gamepad] {
+        // Positions in `bindings` of the keys this backend listens to, in the order it is given them.
+        let indices: Vec<usize> = (0..bindings.len())
+            .filter(|&index| bindings[index].key.key.kind() == kind)
+            .collect();
+        if indices.is_empty() {
+            continue;
+        }
+        let keys: Vec<Hotkey> = indices
+            .iter()
+            .map(|&index| Hotkey {
+                chord: bindings[index].key,
+                fallback: bindings[index].fallback,
+                role: match bindings[index].action {
+                    HotkeyAction::Leader(timeout) => Role::Leader(timeout),
+                    HotkeyAction::AfterLeader(_) => Role::Follower,
+                    _ => Role::Always,
+                },
+            })
+            .collect();
+        let dispatch = dispatch.clone();
+        std::thread::spawn(move || {
+            let result = default_backend(kind).and_then(|mut backend| {
+                let dispatch = dispatch.clone();
+                let indices = indices.clone();
+                backend.run(
+                    &keys,
+                    Box::new(move |event| dispatch(event.with_index(|i| indices[i]))),
+                )
+            });
+            // Without a backend none of these keys work; say so instead of dying quietly.
+            if let Err(e) = result {
+                // Followers only matter through their leader, which is reported already.
+                let reported = keys
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, hotkey)| hotkey.role != Role::Follower);
+                for (i, hotkey) in reported {
+                    dispatch(HotkeyEvent::Unavailable {
+                        index: indices[i],
+                        chord: hotkey.chord,
+                        reason: format!("{:#}", e),
+                        suggestions: Vec::new(),
+                        retrying: false,
+                    });
+                }
+            }
+        });
+    }
+
+    Ok(())
+}
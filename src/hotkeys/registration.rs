@@ -0,0 +1,441 @@
+use crate::hotkeys::Hotkey;
+use crate::hotkeys::HotkeyEvent;
+use crate::hotkeys::Role;
+use crate::key::Key;
+use crate::toggle_key::Chord;
+use crate::toggle_key::Modifiers;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How often chords held by another application are tried again.
+pub const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How many free keys to suggest when a chord can't be registered.
+const SUGGESTIONS: usize = 5;
+
+/// Id used to check whether a chord is free, away from the ids of the hotkeys themselves.
+/// Windows reserves ids from 0xC000 for shared DLLs.
+const PROBE_ID: usize = 0xB000;
+
+/// Why the platform refused a chord.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GrabError {
+    /// Another application holds it, and may let go of it later.
+    Taken,
+    /// Anything else, e.g. a key missing from the keyboard layout. Not worth retrying.
+    Other(String),
+}
+
+/// Registers chords with a platform that only lets one application have each.
+pub trait Grabber {
+    fn grab(&mut self, id: usize, chord: &Chord) -> Result<(), GrabError>;
+    fn ungrab(&mut self, id: usize, chord: &Chord);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Active {
+    Primary,
+    Fallback,
+    Nothing,
+}
+
+/// Keeps every hotkey registered through its chord, or its fallback while the chord is taken,
+/// and takes the chord back once its owner lets go.
+pub struct Registrations {
+    hotkeys: Vec<Hotkey>,
+    active: Vec<Active>,
+    /// Whether the primary chord is worth trying again.
+    retry: Vec<bool>,
+    /// When the followers get unregistered again, while a leader is armed.
+    armed_until: Option<Instant>,
+}
+
+impl Registrations {
+    /// The id the primary chord of `hotkeys[index]` is registered under. Ids start at 1.
+    pub fn primary_id(index: usize) -> usize {
+        2 * index + 1
+    }
+
+    pub fn fallback_id(index: usize) -> usize {
+        2 * index + 2
+    }
+
+    /// The position of the hotkey registered under `id`.
+    pub fn index_of(id: usize) -> Option<usize> {
+        id.checked_sub(1).map(|id| id / 2)
+    }
+
+    /// Registers every hotkey but the followers, reporting the ones that only got their fallback
+    /// or nothing.
+    pub fn register(
+        grabber: &mut impl Grabber,
+        hotkeys: &[Hotkey],
+        on_hotkey: &mut dyn FnMut(HotkeyEvent),
+    ) -> Self {
+        let mut registrations = Self {
+            hotkeys: hotkeys.to_vec(),
+            active: vec![Active::Nothing; hotkeys.len()],
+            retry: vec![false; hotkeys.len()],
+            armed_until: None,
+        };
+        for (index, hotkey) in hotkeys.iter().enumerate() {
+            if hotkey.role != Role::Follower {
+                registrations.try_primary(grabber, index, on_hotkey);
+            }
+        }
+        registrations
+    }
+
+    /// The chord currently registered for `hotkeys[index]`, if any.
+    pub fn chord(&self, index: usize) -> Option<Chord> {
+        let hotkey = &self.hotkeys[index];
+        match self.active[index] {
+            Active::Primary => Some(hotkey.chord),
+            Active::Fallback => hotkey.fallback,
+            Active::Nothing => None,
+        }
+    }
+
+    /// Whether some chord is waiting for its owner to let go, so [`Registrations::retry`] should be called.
+    pub fn needs_retry(&self) -> bool {
+        self.retry.iter().any(|&retry| retry)
+    }
+
+    /// Tries the taken chords again, moving off fallbacks where they succeed.
+    pub fn retry(&mut self, grabber: &mut impl Grabber, on_hotkey: &mut dyn FnMut(HotkeyEvent)) {
+        for index in 0..self.hotkeys.len() {
+            if !self.retry[index] {
+                continue;
+            }
+            let hotkey = self.hotkeys[index];
+            match grabber.grab(Self::primary_id(index), &hotkey.chord) {
+                Ok(()) => {
+                    if let (Active::Fallback, Some(fallback)) =
+                        (self.active[index], hotkey.fallback)
+                    {
+                        grabber.ungrab(Self::fallback_id(index), &fallback);
+                    }
+                    self.active[index] = Active::Primary;
+                    self.retry[index] = false;
+                    on_hotkey(HotkeyEvent::Registered {
+                        index,
+                        chord: hotkey.chord,
+                        instead_of: None,
+                    });
+                }
+                Err(GrabError::Taken) => {}
+                Err(GrabError::Other(_)) => self.retry[index] = false,
+            }
+        }
+    }
+
+    /// Registers the followers when a leader is pressed, and unregisters them once one of them is.
+    /// Call this after reporting the press of `hotkeys[index]`.
+    pub fn pressed(&mut self, grabber: &mut impl Grabber, index: usize) {
+        match self.hotkeys[index].role {
+            Role::Always => {}
+            Role::Leader(timeout) => {
+                self.armed_until = Some(Instant::now() + timeout);
+                for index in 0..self.hotkeys.len() {
+                    let hotkey = self.hotkeys[index];
+                    if hotkey.role == Role::Follower && self.active[index] == Active::Nothing {
+                        // A follower another application holds just doesn't work this time.
+                        if grabber.grab(Self::primary_id(index), &hotkey.chord).is_ok() {
+                            self.active[index] = Active::Primary;
+                        }
+                    }
+                }
+            }
+            Role::Follower => self.disarm(grabber),
+        }
+    }
+
+    /// When the armed followers should be unregistered by [`Registrations::expire`], if any are.
+    pub fn armed_until(&self) -> Option<Instant> {
+        self.armed_until
+    }
+
+    /// Unregisters the followers if the leader timed out by `now`.
+    pub fn expire(&mut self, grabber: &mut impl Grabber, now: Instant) {
+        if self.armed_until.is_some_and(|until| now >= until) {
+            self.disarm(grabber);
+        }
+    }
+
+    fn disarm(&mut self, grabber: &mut impl Grabber) {
+        self.armed_until = None;
+        for index in 0..self.hotkeys.len() {
+            let hotkey = self.hotkeys[index];
+            if hotkey.role == Role::Follower && self.active[index] == Active::Primary {
+                grabber.ungrab(Self::primary_id(index), &hotkey.chord);
+                self.active[index] = Active::Nothing;
+            }
+        }
+    }
+
+    /// Releases every registered chord.
+    pub fn unregister(&mut self, grabber: &mut impl Grabber) {
+        for index in 0..self.hotkeys.len() {
+            let hotkey = self.hotkeys[index];
+            match (self.active[index], hotkey.fallback) {
+                (Active::Primary, _) => grabber.ungrab(Self::primary_id(index), &hotkey.chord),
+                (Active::Fallback, Some(fallback)) => {
+                    grabber.ungrab(Self::fallback_id(index), &fallback)
+                }
+                _ => {}
+            }
+            self.active[index] = Active::Nothing;
+        }
+    }
+
+    fn try_primary(
+        &mut self,
+        grabber: &mut impl Grabber,
+        index: usize,
+        on_hotkey: &mut dyn FnMut(HotkeyEvent),
+    ) {
+        let hotkey = self.hotkeys[index];
+        let reason = match grabber.grab(Self::primary_id(index), &hotkey.chord) {
+            Ok(()) => {
+                self.active[index] = Active::Primary;
+                return;
+            }
+            Err(GrabError::Taken) => {
+                self.retry[index] = true;
+                "another application has already registered it".to_string()
+            }
+            Err(GrabError::Other(reason)) => reason,
+        };
+        let suggestions = self.suggest(grabber, &hotkey.chord);
+        on_hotkey(HotkeyEvent::Unavailable {
+            index,
+            chord: hotkey.chord,
+            reason,
+            suggestions,
+            retrying: self.retry[index],
+        });
+        if let Some(fallback) = hotkey.fallback {
+            match grabber.grab(Self::fallback_id(index), &fallback) {
+                Ok(()) => {
+                    self.active[index] = Active::Fallback;
+                    on_hotkey(HotkeyEvent::Registered {
+                        index,
+                        chord: fallback,
+                        instead_of: Some(hotkey.chord),
+                    });
+                }
+                Err(GrabError::Taken | GrabError::Other(_)) => {
+                    on_hotkey(HotkeyEvent::Unavailable {
+                        index,
+                        chord: fallback,
+                        reason: "the fallback couldn't be registered either".to_string(),
+                        suggestions: Vec::new(),
+                        retrying: false,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Chords near `chord` that the platform would accept right now, and that aren't bound already.
+    fn suggest(&self, grabber: &mut impl Grabber, chord: &Chord) -> Vec<Chord> {
+        let bound: Vec<Chord> = self
+            .hotkeys
+            .iter()
+            .flat_map(|hotkey| [Some(hotkey.chord), hotkey.fallback])
+            .flatten()
+            .collect();
+        let same_key = [
+            Modifiers::CTRL | Modifiers::ALT,
+            Modifiers::CTRL | Modifiers::SHIFT,
+            Modifiers::ALT | Modifiers::SHIFT,
+            Modifiers::CTRL | Modifiers::ALT | Modifiers::SHIFT,
+        ]
+        .map(|modifiers| Chord {
+            modifiers,
+            key: chord.key,
+        });
+        let rarely_used =
+            [Key::Pause, Key::ScrollLock, Key::F13, Key::F14, Key::F15].map(|key| Chord {
+                modifiers: Modifiers::NONE,
+                key,
+            });
+        let mut suggestions = Vec::new();
+        for candidate in same_key.into_iter().chain(rarely_used) {
+            if suggestions.len() == SUGGESTIONS {
+                break;
+            }
+            if candidate == *chord || bound.contains(&candidate) {
+                continue;
+            }
+            if grabber.grab(PROBE_ID, &candidate).is_ok() {
+                grabber.ungrab(PROBE_ID, &candidate);
+                suggestions.push(candidate);
+            }
+        }
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Stands in for the platform: chords in `taken` belong to someone else.
+    #[derive(Default)]
+    struct FakeGrabber {
+        taken: Vec<Chord>,
+        grabbed: HashMap<usize, Chord>,
+    }
+
+    impl Grabber for FakeGrabber {
+        fn grab(&mut self, id: usize, chord: &Chord) -> Result<(), GrabError> {
+            if self.taken.contains(chord) || self.grabbed.values().any(|other| other == chord) {
+                return Err(GrabError::Taken);
+            }
+            self.grabbed.insert(id, *chord);
+            Ok(())
+        }
+
+        fn ungrab(&mut self, id: usize, chord: &Chord) {
+            assert_eq!(self.grabbed.remove(&id), Some(*chord));
+        }
+    }
+
+    fn chord(modifiers: Modifiers, key: Key) -> Chord {
+        Chord { modifiers, key }
+    }
+
+    fn events(
+        run: impl FnOnce(&mut dyn FnMut(HotkeyEvent)) -> Registrations,
+    ) -> (Registrations, Vec<HotkeyEvent>) {
+        let mut events = Vec::new();
+        let registrations = run(&mut |event| events.push(event));
+        (registrations, events)
+    }
+
+    #[test]
+    fn taken_chord_falls_back_and_is_taken_back_when_freed() {
+        let f9 = chord(Modifiers::NONE, Key::F9);
+        let f10 = chord(Modifiers::NONE, Key::F10);
+        let mut grabber = FakeGrabber {
+            taken: vec![f9],
+            ..Default::default()
+        };
+        let hotkeys = [Hotkey {
+            chord: f9,
+            fallback: Some(f10),
+            role: Role::Always,
+        }];
+        let (mut registrations, reported) =
+            events(|on_hotkey| Registrations::register(&mut grabber, &hotkeys, on_hotkey));
+        assert!(matches!(
+            &reported[..],
+            [
+                HotkeyEvent::Unavailable { index: 0, retrying: true, .. },
+                HotkeyEvent::Registered { index: 0, chord, instead_of: Some(_) },
+            ] if *chord == f10
+        ));
+        assert_eq!(registrations.chord(0), Some(f10));
+
+        // Still taken: nothing changes.
+        let mut reported = Vec::new();
+        registrations.retry(&mut grabber, &mut |event| reported.push(event));
+        assert_eq!(reported, []);
+
+        grabber.taken.clear();
+        registrations.retry(&mut grabber, &mut |event| reported.push(event));
+        assert_eq!(
+            reported,
+            [HotkeyEvent::Registered {
+                index: 0,
+                chord: f9,
+                instead_of: None
+            }]
+        );
+        assert_eq!(registrations.chord(0), Some(f9));
+        assert!(!registrations.needs_retry());
+        assert_eq!(grabber.grabbed.values().collect::<Vec<_>>(), [&f9]);
+
+        registrations.unregister(&mut grabber);
+        assert!(grabber.grabbed.is_empty());
+    }
+
+    #[test]
+    fn suggestions_are_free_and_unbound() {
+        let ctrl_alt_l = chord(Modifiers::CTRL | Modifiers::ALT, Key::L);
+        let ctrl_shift_l = chord(Modifiers::CTRL | Modifiers::SHIFT, Key::L);
+        let alt_shift_l = chord(Modifiers::ALT | Modifiers::SHIFT, Key::L);
+        let mut grabber = FakeGrabber {
+            taken: vec![ctrl_alt_l, ctrl_shift_l],
+            ..Default::default()
+        };
+        let hotkeys = [
+            Hotkey {
+                chord: ctrl_alt_l,
+                fallback: None,
+                role: Role::Always,
+            },
+            Hotkey {
+                chord: alt_shift_l,
+                fallback: None,
+                role: Role::Always,
+            },
+        ];
+        let (registrations, reported) =
+            events(|on_hotkey| Registrations::register(&mut grabber, &hotkeys, on_hotkey));
+        let [HotkeyEvent::Unavailable { suggestions, .. }] = &reported[..] else {
+            panic!("unexpected events {:?}", reported);
+        };
+        assert_eq!(
+            suggestions[..2],
+            [
+                chord(Modifiers::CTRL | Modifiers::ALT | Modifiers::SHIFT, Key::L),
+                chord(Modifiers::NONE, Key::Pause),
+            ]
+        );
+        assert_eq!(suggestions.len(), SUGGESTIONS);
+        assert_eq!(registrations.chord(0), None);
+        // Probing left nothing behind.
+        assert_eq!(grabber.grabbed.len(), 1);
+    }
+
+    #[test]
+    fn followers_are_only_registered_while_the_leader_is_armed() {
+        let leader = chord(Modifiers::NONE, Key::ScrollLock);
+        let one = chord(Modifiers::NONE, Key::Digit1);
+        let mut grabber = FakeGrabber::default();
+        let hotkeys = [
+            Hotkey {
+                chord: leader,
+                fallback: None,
+                role: Role::Leader(Duration::from_secs(60)),
+            },
+            Hotkey {
+                chord: one,
+                fallback: None,
+                role: Role::Follower,
+            },
+        ];
+        let (mut registrations, reported) =
+            events(|on_hotkey| Registrations::register(&mut grabber, &hotkeys, on_hotkey));
+        assert_eq!(reported, []);
+        assert_eq!(grabber.grabbed.values().collect::<Vec<_>>(), [&leader]);
+
+        registrations.pressed(&mut grabber, 0);
+        assert_eq!(registrations.chord(1), Some(one));
+        // Not timed out yet.
+        registrations.expire(&mut grabber, Instant::now());
+        assert_eq!(registrations.chord(1), Some(one));
+
+        registrations.pressed(&mut grabber, 1);
+        assert_eq!(registrations.chord(1), None);
+        assert_eq!(registrations.armed_until(), None);
+
+        registrations.pressed(&mut grabber, 0);
+        let until = registrations.armed_until().unwrap();
+        registrations.expire(&mut grabber, until);
+        assert_eq!(grabber.grabbed.values().collect::<Vec<_>>(), [&leader]);
+    }
+}
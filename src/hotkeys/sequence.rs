@@ -0,0 +1,168 @@
+use crate::controller::Command;
+use crate::controller::ControllerHandle;
+use crate::key::Key;
+use std::fmt;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+use std::time::Instant;
+
+/// What a key pressed right after the leader asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Unlock,
+    /// Lock to the monitor at this position in `cursor-lock list`, counting from 0.
+    Monitor(usize),
+}
+
+impl Step {
+    /// The keys that may follow the leader: `1`-`9` pick a monitor and `0` unlocks.
+    pub fn all() -> [(Key, Step); 10] {
+        [
+            (Key::Digit0, Step::Unlock),
+            (Key::Digit1, Step::Monitor(0)),
+            (Key::Digit2, Step::Monitor(1)),
+            (Key::Digit3, Step::Monitor(2)),
+            (Key::Digit4, Step::Monitor(3)),
+            (Key::Digit5, Step::Monitor(4)),
+            (Key::Digit6, Step::Monitor(5)),
+            (Key::Digit7, Step::Monitor(6)),
+            (Key::Digit8, Step::Monitor(7)),
+            (Key::Digit9, Step::Monitor(8)),
+        ]
+    }
+
+    fn command(self) -> Command {
+        match self {
+            Step::Unlock => Command::Unlock,
+            Step::Monitor(index) => Command::LockToMonitor(index),
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Unlock => write!(f, "unlock"),
+            Step::Monitor(index) => write!(f, "monitor {}", index + 1),
+        }
+    }
+}
+
+/// Whether the leader is armed, i.e. was pressed recently enough that a step completes the sequence.
+/// Time only moves through the instants passed in, so it can be driven by a fake clock.
+struct Sequencer {
+    timeout: Duration,
+    armed_at: Option<Instant>,
+}
+
+impl Sequencer {
+    fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            armed_at: None,
+        }
+    }
+
+    /// Arms the leader, or re-arms it with a fresh timeout.
+    fn leader(&mut self, at: Instant) {
+        self.armed_at = Some(at);
+    }
+
+    /// The step that completes the sequence, if the leader is armed. Either way the leader is disarmed.
+    fn step(&mut self, step: Step, at: Instant) -> Option<Step> {
+        let armed_at = self.armed_at.take()?;
+        (at < armed_at + self.timeout).then_some(step)
+    }
+
+    /// Disarms the leader if `now` is past its timeout, returning whether it did.
+    fn poll(&mut self, now: Instant) -> bool {
+        match self.deadline() {
+            Some(deadline) if now >= deadline => {
+                self.armed_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// When [`Sequencer::poll`] should next be called, if the leader is armed.
+    fn deadline(&self) -> Option<Instant> {
+        Some(self.armed_at? + self.timeout)
+    }
+}
+
+/// Spawns a thread that follows leader presses (`None`) and steps in `events`, and sends the
+/// completed sequences to the controller.
+pub fn spawn_sequencer(
+    controller: ControllerHandle,
+    timeout: Duration,
+    events: Receiver<(Option<Step>, Instant)>,
+) {
+    std::thread::spawn(move || {
+        let mut sequencer = Sequencer::new(timeout);
+        loop {
+            let event = match sequencer.deadline() {
+                Some(deadline) => {
+                    match events.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => {
+                            if sequencer.poll(Instant::now()) {
+                                controller.send(Command::LeaderArmed(false));
+                            }
+                            continue;
+                        }
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                None => match events.recv() {
+                    Ok(event) => event,
+                    Err(_) => return,
+                },
+            };
+            match event {
+                (None, at) => {
+                    sequencer.leader(at);
+                    controller.send(Command::LeaderArmed(true));
+                }
+                (Some(step), at) => {
+                    if let Some(step) = sequencer.step(step, at) {
+                        controller.send(step.command());
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_only_count_right_after_the_leader() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut sequencer = Sequencer::new(Duration::from_millis(1500));
+
+        assert_eq!(sequencer.step(Step::Monitor(1), at(0)), None);
+        sequencer.leader(at(100));
+        assert_eq!(sequencer.deadline(), Some(at(1600)));
+        assert_eq!(
+            sequencer.step(Step::Monitor(1), at(500)),
+            Some(Step::Monitor(1))
+        );
+        // One step per leader press.
+        assert_eq!(sequencer.step(Step::Unlock, at(600)), None);
+
+        sequencer.leader(at(1000));
+        assert!(!sequencer.poll(at(2499)));
+        assert!(sequencer.poll(at(2500)));
+        assert_eq!(sequencer.step(Step::Unlock, at(2600)), None);
+
+        // A step that arrives late without a poll in between doesn't count either.
+        sequencer.leader(at(3000));
+        assert_eq!(sequencer.step(Step::Unlock, at(4500)), None);
+        assert_eq!(sequencer.deadline(), None);
+    }
+}
@@ -0,0 +1,456 @@
+use crate::hotkeys::registration::GrabError;
+use crate::hotkeys::registration::Grabber;
+use crate::hotkeys::registration::Registrations;
+use crate::hotkeys::registration::RETRY_INTERVAL;
+use crate::hotkeys::GlobalHotkeys;
+use crate::hotkeys::Hotkey;
+use crate::hotkeys::HotkeyEvent;
+use crate::key::Key;
+use crate::toggle_key::Chord;
+use crate::toggle_key::Modifiers;
+use eyre::Context;
+use eyre::Result;
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::ERROR_HOTKEY_ALREADY_REGISTERED;
+use windows::Win32::Foundation::HWND;
+use windows::Win32::Foundation::LPARAM;
+use windows::Win32::Foundation::LRESULT;
+use windows::Win32::Foundation::WPARAM;
+use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows::Win32::System::Threading::GetCurrentThreadId;
+use windows::Win32::UI::Input::KeyboardAndMouse::GetAsyncKeyState;
+use windows::Win32::UI::Input::KeyboardAndMouse::RegisterHotKey;
+use windows::Win32::UI::Input::KeyboardAndMouse::UnregisterHotKey;
+use windows::Win32::UI::Input::KeyboardAndMouse::HOT_KEY_MODIFIERS;
+use windows::Win32::UI::Input::KeyboardAndMouse::MOD_ALT;
+use windows::Win32::UI::Input::KeyboardAndMouse::MOD_CONTROL;
+use windows::Win32::UI::Input::KeyboardAndMouse::MOD_NOREPEAT;
+use windows::Win32::UI::Input::KeyboardAndMouse::MOD_SHIFT;
+use windows::Win32::UI::Input::KeyboardAndMouse::MOD_WIN;
+use windows::Win32::UI::Input::XboxController::XInputGetState;
+use windows::Win32::UI::Input::XboxController::XINPUT_GAMEPAD_A;
+use windows::Win32::UI::Input::XboxController::XINPUT_GAMEPAD_B;
+use windows::Win32::UI::Input::XboxController::XINPUT_GAMEPAD_BACK;
+use windows::Win32::UI::Input::XboxController::XINPUT_GAMEPAD_BUTTON_FLAGS;
+use windows::Win32::UI::Input::XboxController::XINPUT_GAMEPAD_LEFT_SHOULDER;
+use windows::Win32::UI::Input::XboxController::XINPUT_GAMEPAD_LEFT_THUMB;
+use windows::Win32::UI::Input::XboxController::XINPUT_GAMEPAD_RIGHT_SHOULDER;
+use windows::Win32::UI::Input::XboxController::XINPUT_GAMEPAD_RIGHT_THUMB;
+use windows::Win32::UI::Input::XboxController::XINPUT_GAMEPAD_START;
+use windows::Win32::UI::Input::XboxController::XINPUT_GAMEPAD_X;
+use windows::Win32::UI::Input::XboxController::XINPUT_GAMEPAD_Y;
+use windows::Win32::UI::Input::XboxController::XINPUT_STATE;
+use windows::Win32::UI::Input::XboxController::XUSER_MAX_COUNT;
+use windows::Win32::UI::WindowsAndMessaging::CallNextHookEx;
+use windows::Win32::UI::WindowsAndMessaging::CreateWindowExW;
+use windows::Win32::UI::WindowsAndMessaging::DefWindowProcW;
+use windows::Win32::UI::WindowsAndMessaging::DestroyWindow;
+use windows::Win32::UI::WindowsAndMessaging::DispatchMessageW;
+use windows::Win32::UI::WindowsAndMessaging::GetMessageW;
+use windows::Win32::UI::WindowsAndMessaging::KillTimer;
+use windows::Win32::UI::WindowsAndMessaging::PostQuitMessage;
+use windows::Win32::UI::WindowsAndMessaging::PostThreadMessageW;
+use windows::Win32::UI::WindowsAndMessaging::RegisterClassW;
+use windows::Win32::UI::WindowsAndMessaging::SetTimer;
+use windows::Win32::UI::WindowsAndMessaging::SetWindowsHookExW;
+use windows::Win32::UI::WindowsAndMessaging::TranslateMessage;
+use windows::Win32::UI::WindowsAndMessaging::UnhookWindowsHookEx;
+use windows::Win32::UI::WindowsAndMessaging::CW_USEDEFAULT;
+use windows::Win32::UI::WindowsAndMessaging::HC_ACTION;
+use windows::Win32::UI::WindowsAndMessaging::MSG;
+use windows::Win32::UI::WindowsAndMessaging::MSLLHOOKSTRUCT;
+use windows::Win32::UI::WindowsAndMessaging::WH_MOUSE_LL;
+use windows::Win32::UI::WindowsAndMessaging::WM_APP;
+use windows::Win32::UI::WindowsAndMessaging::WM_DESTROY;
+use windows::Win32::UI::WindowsAndMessaging::WM_HOTKEY;
+use windows::Win32::UI::WindowsAndMessaging::WM_MBUTTONDOWN;
+use windows::Win32::UI::WindowsAndMessaging::WM_MBUTTONUP;
+use windows::Win32::UI::WindowsAndMessaging::WM_TIMER;
+use windows::Win32::UI::WindowsAndMessaging::WM_XBUTTONDOWN;
+use windows::Win32::UI::WindowsAndMessaging::WM_XBUTTONUP;
+use windows::Win32::UI::WindowsAndMessaging::WNDCLASSW;
+use windows::Win32::UI::WindowsAndMessaging::XBUTTON1;
+use windows::Win32::UI::WindowsAndMessaging::XBUTTON2;
+
+/// This is our window procedure for the hidden window. We don’t need to process many messages;
+/// just WM_HOTKEY and WM_DESTROY.
+extern "system" fn wnd_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    match msg {
+        WM_HOTKEY => {
+            // We'll process WM_HOTKEY in our message loop in the spawned thread.
+            // (We can simply call DefWindowProcW here.)
+            unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) }
+        }
+        WM_DESTROY => {
+            unsafe {
+                PostQuitMessage(0);
+            }
+            LRESULT(0)
+        }
+        _ => unsafe { DefWindowProcW(hwnd, msg, wparam, lparam) },
+    }
+}
+
+/// Creates a hidden window that will be used to receive hotkey messages.
+fn create_message_window() -> Result<HWND> {
+    // Define a class name (must be wide string).
+    let class_name = "HiddenHotkeyWindow";
+    let class_name_w: Vec<u16> = class_name.encode_utf16().chain(Some(0)).collect();
+
+    unsafe {
+        let hinstance = GetModuleHandleW(None)?;
+        let wnd_class = WNDCLASSW {
+            hInstance: hinstance.into(),
+            lpszClassName: PCWSTR(class_name_w.as_ptr()),
+            lpfnWndProc: Some(wnd_proc),
+            ..Default::default()
+        };
+
+        // Register the window class.
+        let atom = RegisterClassW(&wnd_class);
+        if atom == 0 {
+            eyre::bail!("Failed to register window class");
+        }
+
+        // Create a hidden window.
+        let hwnd = CreateWindowExW(
+            Default::default(),
+            PCWSTR(class_name_w.as_ptr()),
+            PCWSTR(class_name_w.as_ptr()),
+            Default::default(),
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            CW_USEDEFAULT,
+            HWND::default(), // No parent.
+            None,            // No menu.
+            hinstance,
+            None, // No additional parameters.
+        )?;
+
+        if hwnd.0.is_null() {
+            eyre::bail!("Failed to create hidden window");
+        }
+        Ok(hwnd)
+    }
+}
+
+/// Translates a chord's modifiers for `RegisterHotKey`. `MOD_NOREPEAT` keeps a held key from
+/// toggling over and over as it auto-repeats.
+fn hotkey_modifiers(modifiers: Modifiers) -> HOT_KEY_MODIFIERS {
+    let mut flags = MOD_NOREPEAT;
+    for (modifier, flag) in [
+        (Modifiers::CTRL, MOD_CONTROL),
+        (Modifiers::ALT, MOD_ALT),
+        (Modifiers::SHIFT, MOD_SHIFT),
+        (Modifiers::SUPER, MOD_WIN),
+    ] {
+        if modifiers.contains(modifier) {
+            flags |= flag;
+        }
+    }
+    flags
+}
+
+/// `WM_HOTKEY` only reports presses, so while a hotkey is held this timer polls for its release.
+const RELEASE_TIMER: usize = 1;
+const RELEASE_POLL_MS: u32 = 15;
+
+/// Runs while some chord belongs to another application, to take it once they let go.
+const RETRY_TIMER: usize = 2;
+
+/// Fires when an armed leader times out, to unregister its followers.
+const LEADER_TIMER: usize = 3;
+
+/// Whether the key with this virtual-key code is physically down right now.
+fn is_key_down(vk: u32) -> bool {
+    // The most significant bit is set while the key is down.
+    unsafe { GetAsyncKeyState(vk as i32) < 0 }
+}
+
+/// Schedules [`LEADER_TIMER`] for when an armed leader times out, or stops it if none is armed.
+/// Timers may fire a little early, so the followers are only unregistered once it is really time.
+fn arm_leader_timer(hwnd: HWND, armed_until: Option<Instant>) {
+    unsafe {
+        match armed_until {
+            Some(until) => {
+                let timeout = until.saturating_duration_since(Instant::now());
+                SetTimer(hwnd, LEADER_TIMER, timeout.as_millis() as u32 + 1, None);
+            }
+            None => _ = KillTimer(hwnd, LEADER_TIMER),
+        }
+    }
+}
+
+/// Registers chords with `RegisterHotKey` on our hidden window.
+struct HotkeyWindow(HWND);
+
+impl Grabber for HotkeyWindow {
+    fn grab(&mut self, id: usize, chord: &Chord) -> Result<(), GrabError> {
+        let modifiers = hotkey_modifiers(chord.modifiers);
+        unsafe { RegisterHotKey(self.0, id as i32, modifiers, chord.key.to_vk()) }.map_err(|e| {
+            if e.code() == ERROR_HOTKEY_ALREADY_REGISTERED.to_hresult() {
+                GrabError::Taken
+            } else {
+                GrabError::Other(e.message())
+            }
+        })
+    }
+
+    fn ungrab(&mut self, id: usize, chord: &Chord) {
+        if let Err(e) = unsafe { UnregisterHotKey(self.0, id as i32) } {
+            eprintln!("Error unregistering hotkey {}: {:?}", chord, e);
+        }
+    }
+}
+
+/// Listens for global hotkeys registered with `RegisterHotKey` on a hidden window.
+pub struct Win32Hotkeys;
+
+impl GlobalHotkeys for Win32Hotkeys {
+    fn run(
+        &mut self,
+        keys: &[Hotkey],
+        mut on_hotkey: Box<dyn FnMut(HotkeyEvent) + Send>,
+    ) -> Result<()> {
+        // Create the hidden message window.
+        let hwnd = create_message_window()?;
+        let mut window = HotkeyWindow(hwnd);
+        let mut registrations = Registrations::register(&mut window, keys, &mut on_hotkey);
+        if registrations.needs_retry() {
+            unsafe { SetTimer(hwnd, RETRY_TIMER, RETRY_INTERVAL.as_millis() as u32, None) };
+        }
+
+        // Positions of the hotkeys that are down, waiting for their key to come back up.
+        let mut held: Vec<usize> = Vec::new();
+        let mut msg = MSG::default();
+        loop {
+            unsafe {
+                // Block until a message is received.
+                if GetMessageW(&mut msg, hwnd, 0, 0).as_bool() {
+                    if msg.message == WM_HOTKEY {
+                        let index = Registrations::index_of(msg.wParam.0)
+                            .filter(|&index| index < keys.len());
+                        if let Some(index) = index {
+                            if !held.contains(&index) {
+                                if held.is_empty() {
+                                    SetTimer(hwnd, RELEASE_TIMER, RELEASE_POLL_MS, None);
+                                }
+                                held.push(index);
+                                on_hotkey(HotkeyEvent::Pressed(index));
+                                registrations.pressed(&mut window, index);
+                                arm_leader_timer(hwnd, registrations.armed_until());
+                            }
+                        }
+                    } else if msg.message == WM_TIMER && msg.wParam.0 == RELEASE_TIMER {
+                        held.retain(|&index| {
+                            let down = registrations
+                                .chord(index)
+                                .is_some_and(|chord| is_key_down(chord.key.to_vk()));
+                            if !down {
+                                on_hotkey(HotkeyEvent::Released(index));
+                            }
+                            down
+                        });
+                        if held.is_empty() {
+                            _ = KillTimer(hwnd, RELEASE_TIMER);
+                        }
+                    } else if msg.message == WM_TIMER && msg.wParam.0 == LEADER_TIMER {
+                        registrations.expire(&mut window, Instant::now());
+                        arm_leader_timer(hwnd, registrations.armed_until());
+                    } else if msg.message == WM_TIMER && msg.wParam.0 == RETRY_TIMER {
+                        registrations.retry(&mut window, &mut on_hotkey);
+                        if !registrations.needs_retry() {
+                            _ = KillTimer(hwnd, RETRY_TIMER);
+                        }
+                    }
+                    _ = TranslateMessage(&msg);
+                    DispatchMessageW(&msg);
+                } else {
+                    break;
+                }
+            }
+        }
+        // Unregister the hotkeys and destroy the hidden window.
+        registrations.unregister(&mut window);
+        unsafe { DestroyWindow(hwnd)? };
+
+        Ok(())
+    }
+}
+
+/// The modifiers physically held right now, for inputs that don't report them.
+fn held_modifiers() -> Modifiers {
+    let mut modifiers = Modifiers::NONE;
+    for (modifier, keys) in [
+        (Modifiers::CTRL, [Key::LeftCtrl, Key::RightCtrl]),
+        (Modifiers::ALT, [Key::LeftAlt, Key::RightAlt]),
+        (Modifiers::SHIFT, [Key::LeftShift, Key::RightShift]),
+        (Modifiers::SUPER, [Key::LeftSuper, Key::RightSuper]),
+    ] {
+        if keys.iter().any(|key| is_key_down(key.to_vk())) {
+            modifiers |= modifier;
+        }
+    }
+    modifiers
+}
+
+/// Posted by the mouse hook to its own thread, with the button's virtual-key code and whether it went down.
+const WM_MOUSE_BUTTON: u32 = WM_APP + 1;
+
+/// Sees every mouse event before it reaches a window and forwards the bindable buttons to the
+/// message loop, leaving the click itself alone.
+unsafe extern "system" fn mouse_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code == HC_ACTION as i32 {
+        let info = unsafe { &*(lparam.0 as *const MSLLHOOKSTRUCT) };
+        let message = wparam.0 as u32;
+        let button = match message {
+            WM_MBUTTONDOWN | WM_MBUTTONUP => Some(Key::MouseMiddle),
+            WM_XBUTTONDOWN | WM_XBUTTONUP => match (info.mouseData >> 16) as u16 {
+                XBUTTON1 => Some(Key::MouseBack),
+                XBUTTON2 => Some(Key::MouseForward),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(button) = button {
+            let down = matches!(message, WM_MBUTTONDOWN | WM_XBUTTONDOWN);
+            // The hook must return quickly, so the work happens in the message loop.
+            unsafe {
+                _ = PostThreadMessageW(
+                    GetCurrentThreadId(),
+                    WM_MOUSE_BUTTON,
+                    WPARAM(button.to_vk() as usize),
+                    LPARAM(down as isize),
+                );
+            }
+        }
+    }
+    unsafe { CallNextHookEx(None, code, wparam, lparam) }
+}
+
+/// Listens for mouse buttons through a low-level mouse hook, since `RegisterHotKey` only takes keys.
+pub struct Win32MouseButtons;
+
+impl GlobalHotkeys for Win32MouseButtons {
+    fn run(
+        &mut self,
+        keys: &[Hotkey],
+        mut on_hotkey: Box<dyn FnMut(HotkeyEvent) + Send>,
+    ) -> Result<()> {
+        // The hook sees every button whoever else listens for it, so fallbacks are never needed.
+        let keys: Vec<Chord> = keys.iter().map(|hotkey| hotkey.chord).collect();
+        let hook = unsafe { SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_hook), None, 0) }
+            .wrap_err("Failed to install the mouse hook")?;
+
+        // Buttons currently held down and the chord each one pressed.
+        let mut held = HashMap::new();
+        let mut msg = MSG::default();
+        // Low-level hooks are called through this thread's message loop.
+        while unsafe { GetMessageW(&mut msg, None, 0, 0) }.as_bool() {
+            if msg.message != WM_MOUSE_BUTTON {
+                continue;
+            }
+            let Some(button) = Key::from_vk(msg.wParam.0 as u32) else {
+                continue;
+            };
+            if msg.lParam.0 != 0 {
+                if held.contains_key(&button) {
+                    continue;
+                }
+                let modifiers = held_modifiers();
+                let index = keys
+                    .iter()
+                    .position(|chord| chord.key == button && chord.modifiers == modifiers);
+                held.insert(button, index);
+                if let Some(index) = index {
+                    on_hotkey(HotkeyEvent::Pressed(index));
+                }
+            } else if let Some(Some(index)) = held.remove(&button) {
+                on_hotkey(HotkeyEvent::Released(index));
+            }
+        }
+        unsafe { UnhookWindowsHookEx(hook)? };
+        Ok(())
+    }
+}
+
+/// How often connected controllers are polled; XInput has no events.
+const GAMEPAD_POLL_INTERVAL: Duration = Duration::from_millis(15);
+
+/// The XInput button flag behind a gamepad key.
+fn xinput_button(key: Key) -> Option<XINPUT_GAMEPAD_BUTTON_FLAGS> {
+    Some(match key {
+        Key::PadSouth => XINPUT_GAMEPAD_A,
+        Key::PadEast => XINPUT_GAMEPAD_B,
+        Key::PadWest => XINPUT_GAMEPAD_X,
+        Key::PadNorth => XINPUT_GAMEPAD_Y,
+        Key::PadLeftBumper => XINPUT_GAMEPAD_LEFT_SHOULDER,
+        Key::PadRightBumper => XINPUT_GAMEPAD_RIGHT_SHOULDER,
+        Key::PadStart => XINPUT_GAMEPAD_START,
+        Key::PadSelect => XINPUT_GAMEPAD_BACK,
+        Key::PadLeftStick => XINPUT_GAMEPAD_LEFT_THUMB,
+        Key::PadRightStick => XINPUT_GAMEPAD_RIGHT_THUMB,
+        _ => return None,
+    })
+}
+
+/// Listens for gamepad buttons by polling every XInput controller slot, so controllers can come
+/// and go while we run.
+pub struct XInputButtons;
+
+impl GlobalHotkeys for XInputButtons {
+    fn run(
+        &mut self,
+        keys: &[Hotkey],
+        mut on_hotkey: Box<dyn FnMut(HotkeyEvent) + Send>,
+    ) -> Result<()> {
+        let keys: Vec<Chord> = keys.iter().map(|hotkey| hotkey.chord).collect();
+        let flags = keys
+            .iter()
+            .map(|chord| {
+                xinput_button(chord.key)
+                    .map(|flag| flag.0)
+                    .ok_or_else(|| eyre::eyre!("{} is not a gamepad button", chord))
+            })
+            .collect::<Result<Vec<u16>>>()?;
+
+        // The buttons each controller had down at the last poll.
+        let mut previous = [0u16; XUSER_MAX_COUNT as usize];
+        // Buttons that pressed a chord and are still down, by controller, with the chord's position.
+        let mut pressed: HashMap<(usize, u16), usize> = HashMap::new();
+        loop {
+            for (user, previous) in previous.iter_mut().enumerate() {
+                let mut state = XINPUT_STATE::default();
+                // A disconnected controller counts as having everything released.
+                let buttons = match unsafe { XInputGetState(user as u32, &mut state) } {
+                    0 => state.Gamepad.wButtons.0,
+                    _ => 0,
+                };
+                let went_down = buttons & !*previous;
+                let went_up = *previous & !buttons;
+                *previous = buttons;
+                for flag in (0..16).map(|bit| 1u16 << bit) {
+                    if went_up & flag != 0 {
+                        if let Some(index) = pressed.remove(&(user, flag)) {
+                            on_hotkey(HotkeyEvent::Released(index));
+                        }
+                    }
+                    if went_down & flag != 0 {
+                        let modifiers = held_modifiers();
+                        if let Some(index) = (0..keys.len()).find(|&index| {
+                            flags[index] == flag && keys[index].modifiers == modifiers
+                        }) {
+                            pressed.insert((user, flag), index);
+                            on_hotkey(HotkeyEvent::Pressed(index));
+                        }
+                    }
+                }
+            }
+            std::thread::sleep(GAMEPAD_POLL_INTERVAL);
+        }
+    }
+}
@@ -0,0 +1,568 @@
+use crate::hotkeys::registration::GrabError;
+use crate::hotkeys::registration::Grabber;
+use crate::hotkeys::registration::Registrations;
+use crate::hotkeys::registration::RETRY_INTERVAL;
+use crate::hotkeys::GlobalHotkeys;
+use crate::hotkeys::Hotkey;
+use crate::hotkeys::HotkeyEvent;
+use crate::key::Key;
+use crate::toggle_key::Chord;
+use crate::toggle_key::Modifiers;
+use eyre::Result;
+use std::collections::HashMap;
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+use std::time::Instant;
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyError;
+use x11rb::protocol::xinput;
+use x11rb::protocol::xinput::ConnectionExt as _;
+use x11rb::protocol::xkb;
+use x11rb::protocol::xkb::ConnectionExt as _;
+use x11rb::protocol::xproto::ConnectionExt as _;
+use x11rb::protocol::xproto::GrabMode;
+use x11rb::protocol::xproto::Keycode;
+use x11rb::protocol::xproto::ModMask;
+use x11rb::protocol::xproto::Window;
+use x11rb::protocol::ErrorKind;
+use x11rb::protocol::Event;
+use x11rb::rust_connection::RustConnection;
+
+// Keysyms used to find which ModN bit the server assigned to each modifier.
+const XK_NUM_LOCK: u32 = 0xFF7F;
+const XK_ALT_L: u32 = 0xFFE9;
+const XK_ALT_R: u32 = 0xFFEA;
+const XK_META_L: u32 = 0xFFE7;
+const XK_META_R: u32 = 0xFFE8;
+const XK_SUPER_L: u32 = 0xFFEB;
+const XK_SUPER_R: u32 = 0xFFEC;
+
+/// Listens for global hotkeys grabbed with `XGrabKey` on the root window.
+pub struct X11Hotkeys {
+    conn: RustConnection,
+    root: Window,
+}
+
+/// Where the server put the modifiers that have no fixed bit in the core protocol.
+struct ModifierMasks {
+    alt: u16,
+    super_: u16,
+    num_lock: u16,
+}
+
+impl ModifierMasks {
+    /// The core modifier state that holding `modifiers` produces.
+    fn state(&self, modifiers: Modifiers) -> u16 {
+        let mut state = 0;
+        for (modifier, mask) in [
+            (Modifiers::CTRL, u16::from(ModMask::CONTROL)),
+            (Modifiers::ALT, self.alt),
+            (Modifiers::SHIFT, u16::from(ModMask::SHIFT)),
+            (Modifiers::SUPER, self.super_),
+        ] {
+            if modifiers.contains(modifier) {
+                state |= mask;
+            }
+        }
+        state
+    }
+
+    /// Only the eight modifier bits count, minus the locks; the rest are mouse buttons.
+    fn significant(&self, state: u16) -> u16 {
+        state & 0xFF & !(u16::from(ModMask::LOCK) | self.num_lock)
+    }
+}
+
+/// One grabbed chord: the keycodes producing its key and the modifier state it needs.
+struct Grab {
+    keycodes: Vec<Keycode>,
+    state: u16,
+}
+
+/// Grabs chords on behalf of [`Registrations`], remembering what each id holds.
+struct X11Grabber<'a> {
+    hotkeys: &'a X11Hotkeys,
+    masks: ModifierMasks,
+    locks: [u16; 4],
+    grabs: HashMap<usize, Grab>,
+}
+
+impl Grabber for X11Grabber<'_> {
+    fn grab(&mut self, id: usize, chord: &Chord) -> Result<(), GrabError> {
+        let grab = self.hotkeys.grab(chord, &self.masks, &self.locks)?;
+        self.grabs.insert(id, grab);
+        Ok(())
+    }
+
+    fn ungrab(&mut self, id: usize, _chord: &Chord) {
+        if let Some(grab) = self.grabs.remove(&id) {
+            self.hotkeys.ungrab(&grab, &self.locks);
+        }
+    }
+}
+
+impl X11Hotkeys {
+    /// Connects to the display named by `$DISPLAY`.
+    pub fn connect() -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+        Ok(Self { conn, root })
+    }
+
+    /// Returns every keycode whose mapping includes `keysym`, in any shift level.
+    fn keycodes_for(&self, keysym: u32) -> Result<Vec<Keycode>> {
+        let setup = self.conn.setup();
+        let (min, max) = (setup.min_keycode, setup.max_keycode);
+        let mapping = self
+            .conn
+            .get_keyboard_mapping(min, max - min + 1)?
+            .reply()?;
+        let per_keycode = usize::from(mapping.keysyms_per_keycode).max(1);
+        Ok(mapping
+            .keysyms
+            .chunks(per_keycode)
+            .zip(min..=max)
+            .filter(|(keysyms, _)| keysyms.contains(&keysym))
+            .map(|(_, keycode)| keycode)
+            .collect())
+    }
+
+    /// Reads the modifier mapping, falling back to the usual Mod1 = Alt, Mod2 = NumLock, Mod4 = Super.
+    fn modifier_masks(&self) -> Result<ModifierMasks> {
+        let mapping = self.conn.get_modifier_mapping()?.reply()?;
+        let per_modifier = usize::from(mapping.keycodes_per_modifier()).max(1);
+        let mut masks = ModifierMasks {
+            alt: ModMask::M1.into(),
+            super_: ModMask::M4.into(),
+            num_lock: ModMask::M2.into(),
+        };
+        let mod_keycodes = |keysyms: &[u32]| -> Result<Vec<Keycode>> {
+            let mut keycodes = Vec::new();
+            for &keysym in keysyms {
+                keycodes.extend(self.keycodes_for(keysym)?);
+            }
+            Ok(keycodes)
+        };
+        let alt = mod_keycodes(&[XK_ALT_L, XK_ALT_R, XK_META_L, XK_META_R])?;
+        let super_ = mod_keycodes(&[XK_SUPER_L, XK_SUPER_R])?;
+        let num_lock = mod_keycodes(&[XK_NUM_LOCK])?;
+        // Rows 3-7 of the modifier map are Mod1-Mod5.
+        for (row, keycodes) in mapping.keycodes.chunks(per_modifier).enumerate().skip(3) {
+            let mask = 1 << row;
+            let has = |wanted: &[Keycode]| keycodes.iter().any(|k| *k != 0 && wanted.contains(k));
+            if has(&alt) {
+                masks.alt = mask;
+            }
+            if has(&super_) {
+                masks.super_ = mask;
+            }
+            if has(&num_lock) {
+                masks.num_lock = mask;
+            }
+        }
+        Ok(masks)
+    }
+
+    /// Grabs `chord` in every combination of the lock modifiers, so NumLock or CapsLock being on doesn't hide it.
+    /// Nothing stays grabbed if that fails part way.
+    fn grab(&self, chord: &Chord, masks: &ModifierMasks, locks: &[u16]) -> Result<Grab, GrabError> {
+        let other = |e: &dyn std::fmt::Display| GrabError::Other(e.to_string());
+        let keycodes = self
+            .keycodes_for(chord.key.to_keysym())
+            .map_err(|e| other(&e))?;
+        if keycodes.is_empty() {
+            return Err(GrabError::Other(
+                "it is not on the current keyboard layout".to_string(),
+            ));
+        }
+        let grab = Grab {
+            keycodes,
+            state: masks.state(chord.modifiers),
+        };
+        for &keycode in &grab.keycodes {
+            for &lock in locks {
+                let grabbed = self
+                    .conn
+                    .grab_key(
+                        false,
+                        self.root,
+                        ModMask::from(grab.state | lock),
+                        keycode,
+                        GrabMode::ASYNC,
+                        GrabMode::ASYNC,
+                    )
+                    .map_err(|e| other(&e))?
+                    .check();
+                let error = match grabbed {
+                    Ok(()) => continue,
+                    Err(ReplyError::X11Error(e)) if e.error_kind == ErrorKind::Access => {
+                        GrabError::Taken
+                    }
+                    Err(e) => other(&e),
+                };
+                self.ungrab(&grab, locks);
+                return Err(error);
+            }
+        }
+        Ok(grab)
+    }
+
+    fn ungrab(&self, grab: &Grab, locks: &[u16]) {
+        for &keycode in &grab.keycodes {
+            for &lock in locks {
+                // Ungrabbing a combination we never got is harmless.
+                let _ = self
+                    .conn
+                    .ungrab_key(keycode, self.root, ModMask::from(grab.state | lock));
+            }
+        }
+    }
+
+    /// Waits until the server has something for us or `timeout` passes.
+    fn wait_readable(&self, timeout: Duration) -> Result<()> {
+        let mut fd = libc::pollfd {
+            fd: self.conn.stream().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+        if unsafe { libc::poll(&mut fd, 1, timeout) } < 0 {
+            let error = std::io::Error::last_os_error();
+            if error.kind() != std::io::ErrorKind::Interrupted {
+                return Err(error.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GlobalHotkeys for X11Hotkeys {
+    fn run(
+        &mut self,
+        keys: &[Hotkey],
+        mut on_hotkey: Box<dyn FnMut(HotkeyEvent) + Send>,
+    ) -> Result<()> {
+        // Without detectable auto-repeat, holding a key sends release/press pairs that look like new presses.
+        self.conn.xkb_use_extension(1, 0)?.reply()?;
+        let flag = xkb::PerClientFlag::DETECTABLE_AUTO_REPEAT;
+        self.conn
+            .xkb_per_client_flags(
+                xkb::ID::USE_CORE_KBD.into(),
+                flag,
+                flag,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+            )?
+            .reply()?;
+
+        let masks = self.modifier_masks()?;
+        let lock = u16::from(ModMask::LOCK);
+        let locks = [0, lock, masks.num_lock, lock | masks.num_lock];
+        let mut grabber = X11Grabber {
+            hotkeys: self,
+            masks,
+            locks,
+            grabs: HashMap::new(),
+        };
+        let mut registrations = Registrations::register(&mut grabber, keys, &mut on_hotkey);
+        let mut next_retry = Instant::now() + RETRY_INTERVAL;
+
+        // Keycodes currently held down and the chord each one pressed, so repeats of a held key
+        // are ignored and its release is reported even if the modifiers went up first.
+        let mut held = HashMap::new();
+        loop {
+            self.conn.flush()?;
+            let event = match self.conn.poll_for_event()? {
+                Some(event) => event,
+                None => {
+                    // X11 doesn't say when another client ungrabs, so keep trying; and an armed
+                    // leader's followers are ungrabbed when it times out.
+                    let retry_at = registrations.needs_retry().then_some(next_retry);
+                    match retry_at
+                        .into_iter()
+                        .chain(registrations.armed_until())
+                        .min()
+                    {
+                        None => self.conn.wait_for_event()?,
+                        Some(deadline) => {
+                            let now = Instant::now();
+                            if now < deadline {
+                                self.wait_readable(deadline - now)?;
+                                continue;
+                            }
+                            registrations.expire(&mut grabber, now);
+                            if retry_at.is_some_and(|retry_at| now >= retry_at) {
+                                registrations.retry(&mut grabber, &mut on_hotkey);
+                                next_retry = now + RETRY_INTERVAL;
+                            }
+                            continue;
+                        }
+                    }
+                }
+            };
+            match event {
+                Event::KeyPress(event) => {
+                    if held.contains_key(&event.detail) {
+                        continue;
+                    }
+                    let state = grabber.masks.significant(u16::from(event.state));
+                    let index = grabber
+                        .grabs
+                        .iter()
+                        .find(|(_, grab)| {
+                            grab.state == state && grab.keycodes.contains(&event.detail)
+                        })
+                        .and_then(|(&id, _)| Registrations::index_of(id));
+                    held.insert(event.detail, index);
+                    if let Some(index) = index {
+                        on_hotkey(HotkeyEvent::Pressed(index));
+                        registrations.pressed(&mut grabber, index);
+                    }
+                }
+                Event::KeyRelease(event) => {
+                    if let Some(Some(index)) = held.remove(&event.detail) {
+                        on_hotkey(HotkeyEvent::Released(index));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The core protocol number of a mouse button, as reported in button events.
+fn button_number(key: Key) -> Option<u32> {
+    match key {
+        Key::MouseMiddle => Some(2),
+        Key::MouseBack => Some(8),
+        Key::MouseForward => Some(9),
+        _ => None,
+    }
+}
+
+/// Listens for mouse buttons through XInput2 raw events, which reach us whichever window has the
+/// pointer and without taking the click away from it.
+pub struct X11Buttons(X11Hotkeys);
+
+impl X11Buttons {
+    /// Connects to the display named by `$DISPLAY` and checks it speaks XInput 2.1, whose raw
+    /// events are delivered even while another client grabs the pointer.
+    pub fn connect() -> Result<Self> {
+        let hotkeys = X11Hotkeys::connect()?;
+        let version = hotkeys.conn.xinput_xi_query_version(2, 1)?.reply()?;
+        if (version.major_version, version.minor_version) < (2, 1) {
+            eyre::bail!(
+                "the X server only has XInput {}.{}; mouse buttons need 2.1",
+                version.major_version,
+                version.minor_version
+            );
+        }
+        Ok(Self(hotkeys))
+    }
+}
+
+impl GlobalHotkeys for X11Buttons {
+    fn run(
+        &mut self,
+        keys: &[Hotkey],
+        mut on_hotkey: Box<dyn FnMut(HotkeyEvent) + Send>,
+    ) -> Result<()> {
+        let X11Buttons(hotkeys) = self;
+        // Raw events are shared with every client, so fallbacks are never needed.
+        let keys: Vec<Chord> = keys.iter().map(|hotkey| hotkey.chord).collect();
+        let buttons = keys
+            .iter()
+            .map(|chord| {
+                button_number(chord.key)
+                    .ok_or_else(|| eyre::eyre!("{} is not a mouse button", chord))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let masks = hotkeys.modifier_masks()?;
+        let states: Vec<u16> = keys
+            .iter()
+            .map(|chord| masks.state(chord.modifiers))
+            .collect();
+        hotkeys
+            .conn
+            .xinput_xi_select_events(
+                hotkeys.root,
+                &[xinput::EventMask {
+                    deviceid: xinput::Device::ALL_MASTER.into(),
+                    mask: vec![
+                        xinput::XIEventMask::RAW_BUTTON_PRESS
+                            | xinput::XIEventMask::RAW_BUTTON_RELEASE,
+                    ],
+                }],
+            )?
+            .check()?;
+
+        // Buttons currently held down and the chord each one pressed.
+        let mut held = HashMap::new();
+        loop {
+            match hotkeys.conn.wait_for_event()? {
+                Event::XinputRawButtonPress(event) => {
+                    if held.contains_key(&event.detail) || !buttons.contains(&event.detail) {
+                        continue;
+                    }
+                    // Raw events carry no modifier state, so ask for it.
+                    let pointer = hotkeys.conn.query_pointer(hotkeys.root)?.reply()?;
+                    let state = masks.significant(u16::from(pointer.mask));
+                    let index = (0..keys.len())
+                        .find(|&index| buttons[index] == event.detail && states[index] == state);
+                    held.insert(event.detail, index);
+                    if let Some(index) = index {
+                        on_hotkey(HotkeyEvent::Pressed(index));
+                    }
+                }
+                Event::XinputRawButtonRelease(event) => {
+                    if let Some(Some(index)) = held.remove(&event.detail) {
+                        on_hotkey(HotkeyEvent::Released(index));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hotkeys::Role;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use x11rb::protocol::xproto::BUTTON_PRESS_EVENT;
+    use x11rb::protocol::xproto::BUTTON_RELEASE_EVENT;
+    use x11rb::protocol::xproto::KEY_PRESS_EVENT;
+    use x11rb::protocol::xproto::KEY_RELEASE_EVENT;
+    use x11rb::protocol::xtest::ConnectionExt as _;
+
+    fn chord(key: Key) -> Chord {
+        Chord {
+            modifiers: Modifiers::NONE,
+            key,
+        }
+    }
+
+    fn hotkey(key: Key) -> Hotkey {
+        Hotkey {
+            chord: chord(key),
+            fallback: None,
+            role: Role::Always,
+        }
+    }
+
+    /// Presses and releases `key` through XTest, as if typed on the keyboard.
+    fn type_key(key: Key) {
+        let hotkeys = X11Hotkeys::connect().unwrap();
+        let keycode = hotkeys.keycodes_for(key.to_keysym()).unwrap()[0];
+        for kind in [KEY_PRESS_EVENT, KEY_RELEASE_EVENT] {
+            hotkeys
+                .conn
+                .xtest_fake_input(kind, keycode, 0, hotkeys.root, 0, 0, 0)
+                .unwrap();
+        }
+        // A round trip makes sure the server has processed the fake input.
+        hotkeys.conn.get_input_focus().unwrap().reply().unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs an X server with XTest, e.g. `xvfb-run cargo test -- --ignored`"]
+    fn grabbed_key_press_and_release_are_reported() {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut hotkeys = X11Hotkeys::connect().unwrap();
+            let keys = [hotkey(Key::F8), hotkey(Key::F9)];
+            hotkeys
+                .run(&keys, Box::new(move |event| sender.send(event).unwrap()))
+                .unwrap();
+        });
+        // Give the listener time to grab before typing.
+        std::thread::sleep(Duration::from_millis(500));
+        type_key(Key::F9);
+        let timeout = Duration::from_secs(5);
+        assert_eq!(receiver.recv_timeout(timeout), Ok(HotkeyEvent::Pressed(1)));
+        assert_eq!(receiver.recv_timeout(timeout), Ok(HotkeyEvent::Released(1)));
+    }
+
+    #[test]
+    #[ignore = "needs an X server with XTest, e.g. `xvfb-run cargo test -- --ignored`"]
+    fn grab_conflict_falls_back_and_is_retried() {
+        let owner = X11Hotkeys::connect().unwrap();
+        let masks = owner.modifier_masks().unwrap();
+        owner.grab(&chord(Key::F7), &masks, &[0]).unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut hotkeys = X11Hotkeys::connect().unwrap();
+            let keys = [Hotkey {
+                chord: chord(Key::F7),
+                fallback: Some(chord(Key::F6)),
+                role: Role::Always,
+            }];
+            hotkeys
+                .run(&keys, Box::new(move |event| sender.send(event).unwrap()))
+                .unwrap();
+        });
+        let timeout = Duration::from_secs(5);
+        let event = receiver.recv_timeout(timeout).unwrap();
+        assert!(
+            matches!(
+                event,
+                HotkeyEvent::Unavailable {
+                    index: 0,
+                    retrying: true,
+                    ..
+                }
+            ),
+            "{:?}",
+            event
+        );
+        assert_eq!(
+            receiver.recv_timeout(timeout),
+            Ok(HotkeyEvent::Registered {
+                index: 0,
+                chord: chord(Key::F6),
+                instead_of: Some(chord(Key::F7)),
+            })
+        );
+
+        // Closing the owner's connection drops its grab.
+        drop(owner);
+        assert_eq!(
+            receiver.recv_timeout(RETRY_INTERVAL + timeout),
+            Ok(HotkeyEvent::Registered {
+                index: 0,
+                chord: chord(Key::F7),
+                instead_of: None,
+            })
+        );
+    }
+
+    #[test]
+    #[ignore = "needs an X server with XTest and XInput 2.1, e.g. `xvfb-run cargo test -- --ignored`"]
+    fn mouse_button_press_and_release_are_reported() {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buttons = X11Buttons::connect().unwrap();
+            let keys = [hotkey(Key::MouseBack), hotkey(Key::MouseForward)];
+            buttons
+                .run(&keys, Box::new(move |event| sender.send(event).unwrap()))
+                .unwrap();
+        });
+        std::thread::sleep(Duration::from_millis(500));
+
+        let hotkeys = X11Hotkeys::connect().unwrap();
+        for kind in [BUTTON_PRESS_EVENT, BUTTON_RELEASE_EVENT] {
+            hotkeys
+                .conn
+                .xtest_fake_input(kind, 9, 0, hotkeys.root, 0, 0, 0)
+                .unwrap();
+        }
+        hotkeys.conn.get_input_focus().unwrap().reply().unwrap();
+        let timeout = Duration::from_secs(5);
+        assert_eq!(receiver.recv_timeout(timeout), Ok(HotkeyEvent::Pressed(1)));
+        assert_eq!(receiver.recv_timeout(timeout), Ok(HotkeyEvent::Released(1)));
+    }
+}
@@ -0,0 +1,165 @@
+use eyre::Result;
+use interprocess::local_socket::prelude::*;
+use interprocess::local_socket::GenericNamespaced;
+use interprocess::local_socket::ListenerOptions;
+use interprocess::local_socket::Name;
+use interprocess::local_socket::Stream;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::ErrorKind;
+use std::io::Write;
+
+/// A request sent by `cursor-lock lock|unlock|status` to the running instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    Lock,
+    Unlock,
+    Status,
+}
+
+impl Request {
+    fn as_str(self) -> &'static str {
+        match self {
+            Request::Lock => "lock",
+            Request::Unlock => "unlock",
+            Request::Status => "status",
+        }
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        match line.trim() {
+            "lock" => Some(Request::Lock),
+            "unlock" => Some(Request::Unlock),
+            "status" => Some(Request::Status),
+            _ => None,
+        }
+    }
+}
+
+/// The running instance's answer: whether the cursor is locked afterwards, or why the request failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Locked,
+    Unlocked,
+    Error(String),
+}
+
+impl Response {
+    fn to_line(&self) -> String {
+        match self {
+            Response::Locked => "locked\n".to_string(),
+            Response::Unlocked => "unlocked\n".to_string(),
+            Response::Error(message) => format!("error {}\n", message.replace('\n', " ")),
+        }
+    }
+
+    fn parse(line: &str) -> Result<Self> {
+        let line = line.trim_end();
+        match line {
+            "locked" => Ok(Response::Locked),
+            "unlocked" => Ok(Response::Unlocked),
+            _ => match line.strip_prefix("error ") {
+                Some(message) => Ok(Response::Error(message.to_string())),
+                None => eyre::bail!("Unexpected response from cursor-lock: {:?}", line),
+            },
+        }
+    }
+}
+
+/// The socket is per-user, so two users on one machine can each run their own instance.
+fn socket_name() -> Result<Name<'static>> {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default();
+    Ok(format!("cursor-lock-{}.sock", user).to_ns_name::<GenericNamespaced>()?)
+}
+
+/// Spawns a thread answering requests from other `cursor-lock` invocations.
+/// Fails if another instance is already listening.
+pub fn serve(mut handler: impl FnMut(Request) -> Response + Send + 'static) -> Result<()> {
+    let listener = match ListenerOptions::new().name(socket_name()?).create_sync() {
+        Err(e) if e.kind() == ErrorKind::AddrInUse => {
+            eyre::bail!("Another cursor-lock instance is already running")
+        }
+        listener => listener?,
+    };
+    std::thread::spawn(move || {
+        for conn in listener.incoming() {
+            let result = conn.map_err(eyre::Report::from).and_then(|conn| {
+                let mut conn = BufReader::new(conn);
+                let mut line = String::new();
+                conn.read_line(&mut line)?;
+                let response = match Request::parse(&line) {
+                    Some(request) => handler(request),
+                    None => Response::Error(format!("unknown request {:?}", line.trim())),
+                };
+                conn.get_mut().write_all(response.to_line().as_bytes())?;
+                Ok(())
+            });
+            if let Err(e) = result {
+                eprintln!("Error handling IPC request: {:?}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Sends `request` to the running instance. Returns `Ok(None)` if no instance is running.
+pub fn send(request: Request) -> Result<Option<Response>> {
+    let conn = match Stream::connect(socket_name()?) {
+        Ok(conn) => conn,
+        Err(e) if matches!(e.kind(), ErrorKind::NotFound | ErrorKind::ConnectionRefused) => {
+            return Ok(None)
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut conn = BufReader::new(conn);
+    conn.get_mut()
+        .write_all(format!("{}\n", request.as_str()).as_bytes())?;
+    let mut line = String::new();
+    conn.read_line(&mut line)?;
+    Ok(Some(Response::parse(&line)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_round_trip() {
+        for request in [Request::Lock, Request::Unlock, Request::Status] {
+            let line = format!("{}\n", request.as_str());
+            assert_eq!(Request::parse(&line), Some(request));
+        }
+        assert_eq!(Request::parse("  status \r\n"), Some(Request::Status));
+        assert_eq!(Request::parse("Lock\n"), None);
+        assert_eq!(Request::parse("reboot\n"), None);
+    }
+
+    #[test]
+    fn responses_round_trip_on_one_line() {
+        let responses = [
+            Response::Locked,
+            Response::Unlocked,
+            Response::Error("confinement refused".to_string()),
+        ];
+        for response in responses {
+            assert_eq!(Response::parse(&response.to_line()).unwrap(), response);
+        }
+
+        let line = Response::Error("first\nsecond".to_string()).to_line();
+        assert_eq!(line, "error first second\n");
+        assert_eq!(
+            Response::parse(&line).unwrap(),
+            Response::Error("first second".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_responses_are_errors() {
+        let error = Response::parse("maybe\n").unwrap_err().to_string();
+        assert!(error.contains("\"maybe\""), "{}", error);
+        // An instance that hangs up without answering sends nothing at all.
+        assert!(Response::parse("").is_err());
+    }
+}
@@ -0,0 +1,402 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Declares [`Key`] from one table, so that the name and the platform codes of a key can't drift apart.
+/// Keyboard rows are `Variant, "Name", Windows virtual-key code, X11 keysym, Linux evdev code`.
+/// Buttons on other devices have no keysym, so their rows leave it out and sit in a group per [`KeyKind`].
+macro_rules! keys {
+    (
+        Keyboard { $($variant:ident, $name:literal, $vk:literal, $keysym:literal, $evdev:literal;)* }
+        $($kind:ident { $($button:ident, $button_name:literal, $button_vk:literal, $button_evdev:literal;)* })*
+    ) => {
+        /// A physical key or button, independent of the platform that reports or registers it.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Key {
+            $($variant,)*
+            $($($button,)*)*
+        }
+
+        impl Key {
+            /// Every key, in table order.
+            pub const ALL: &'static [Key] = &[$(Key::$variant,)* $($(Key::$button,)*)*];
+
+            /// The canonical name, as accepted by `--key` and printed back.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Key::$variant => $name,)*
+                    $($(Key::$button => $button_name,)*)*
+                }
+            }
+
+            /// Which device the key is on.
+            pub fn kind(self) -> KeyKind {
+                match self {
+                    $(Key::$variant => KeyKind::Keyboard,)*
+                    $($(Key::$button => KeyKind::$kind,)*)*
+                }
+            }
+
+            /// The Windows virtual-key code, for `RegisterHotKey`.
+            #[cfg_attr(not(any(windows, test)), allow(dead_code))]
+            pub fn to_vk(self) -> u32 {
+                match self {
+                    $(Key::$variant => $vk,)*
+                    $($(Key::$button => $button_vk,)*)*
+                }
+            }
+
+            pub fn from_vk(vk: u32) -> Option<Key> {
+                match vk {
+                    $($vk => Some(Key::$variant),)*
+                    $($($button_vk => Some(Key::$button),)*)*
+                    _ => None,
+                }
+            }
+
+            /// The X11 keysym. Letters use the lowercase keysym, which is what unshifted keys produce.
+            /// Mouse and gamepad buttons have none and return `NoSymbol` (0).
+            #[cfg_attr(windows, allow(dead_code))]
+            pub fn to_keysym(self) -> u32 {
+                match self {
+                    $(Key::$variant => $keysym,)*
+                    _ => 0,
+                }
+            }
+
+            #[cfg_attr(not(test), allow(dead_code))]
+            pub fn from_keysym(keysym: u32) -> Option<Key> {
+                match keysym {
+                    $($keysym => Some(Key::$variant),)*
+                    // Uppercase letters are the same key with Shift held.
+                    0x41..=0x5A => Key::from_keysym(keysym + 0x20),
+                    _ => None,
+                }
+            }
+
+            /// The Linux evdev key code from `linux/input-event-codes.h`.
+            #[cfg_attr(windows, allow(dead_code))]
+            pub fn to_evdev(self) -> u16 {
+                match self {
+                    $(Key::$variant => $evdev,)*
+                    $($(Key::$button => $button_evdev,)*)*
+                }
+            }
+
+            #[cfg_attr(windows, allow(dead_code))]
+            pub fn from_evdev(code: u16) -> Option<Key> {
+                match code {
+                    $($evdev => Some(Key::$variant),)*
+                    $($($button_evdev => Some(Key::$button),)*)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+/// The kind of device a [`Key`] is on. Each platform listens to them in a different way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyKind {
+    Keyboard,
+    Mouse,
+    Gamepad,
+}
+
+keys! {
+    Keyboard {
+        A, "A", 0x41, 0x61, 30;
+        B, "B", 0x42, 0x62, 48;
+        C, "C", 0x43, 0x63, 46;
+        D, "D", 0x44, 0x64, 32;
+        E, "E", 0x45, 0x65, 18;
+        F, "F", 0x46, 0x66, 33;
+        G, "G", 0x47, 0x67, 34;
+        H, "H", 0x48, 0x68, 35;
+        I, "I", 0x49, 0x69, 23;
+        J, "J", 0x4A, 0x6A, 36;
+        K, "K", 0x4B, 0x6B, 37;
+        L, "L", 0x4C, 0x6C, 38;
+        M, "M", 0x4D, 0x6D, 50;
+        N, "N", 0x4E, 0x6E, 49;
+        O, "O", 0x4F, 0x6F, 24;
+        P, "P", 0x50, 0x70, 25;
+        Q, "Q", 0x51, 0x71, 16;
+        R, "R", 0x52, 0x72, 19;
+        S, "S", 0x53, 0x73, 31;
+        T, "T", 0x54, 0x74, 20;
+        U, "U", 0x55, 0x75, 22;
+        V, "V", 0x56, 0x76, 47;
+        W, "W", 0x57, 0x77, 17;
+        X, "X", 0x58, 0x78, 45;
+        Y, "Y", 0x59, 0x79, 21;
+        Z, "Z", 0x5A, 0x7A, 44;
+        Digit0, "0", 0x30, 0x30, 11;
+        Digit1, "1", 0x31, 0x31, 2;
+        Digit2, "2", 0x32, 0x32, 3;
+        Digit3, "3", 0x33, 0x33, 4;
+        Digit4, "4", 0x34, 0x34, 5;
+        Digit5, "5", 0x35, 0x35, 6;
+        Digit6, "6", 0x36, 0x36, 7;
+        Digit7, "7", 0x37, 0x37, 8;
+        Digit8, "8", 0x38, 0x38, 9;
+        Digit9, "9", 0x39, 0x39, 10;
+        F1, "F1", 0x70, 0xFFBE, 59;
+        F2, "F2", 0x71, 0xFFBF, 60;
+        F3, "F3", 0x72, 0xFFC0, 61;
+        F4, "F4", 0x73, 0xFFC1, 62;
+        F5, "F5", 0x74, 0xFFC2, 63;
+        F6, "F6", 0x75, 0xFFC3, 64;
+        F7, "F7", 0x76, 0xFFC4, 65;
+        F8, "F8", 0x77, 0xFFC5, 66;
+        F9, "F9", 0x78, 0xFFC6, 67;
+        F10, "F10", 0x79, 0xFFC7, 68;
+        F11, "F11", 0x7A, 0xFFC8, 87;
+        F12, "F12", 0x7B, 0xFFC9, 88;
+        F13, "F13", 0x7C, 0xFFCA, 183;
+        F14, "F14", 0x7D, 0xFFCB, 184;
+        F15, "F15", 0x7E, 0xFFCC, 185;
+        F16, "F16", 0x7F, 0xFFCD, 186;
+        F17, "F17", 0x80, 0xFFCE, 187;
+        F18, "F18", 0x81, 0xFFCF, 188;
+        F19, "F19", 0x82, 0xFFD0, 189;
+        F20, "F20", 0x83, 0xFFD1, 190;
+        F21, "F21", 0x84, 0xFFD2, 191;
+        F22, "F22", 0x85, 0xFFD3, 192;
+        F23, "F23", 0x86, 0xFFD4, 193;
+        F24, "F24", 0x87, 0xFFD5, 194;
+        Numpad0, "Num0", 0x60, 0xFFB0, 82;
+        Numpad1, "Num1", 0x61, 0xFFB1, 79;
+        Numpad2, "Num2", 0x62, 0xFFB2, 80;
+        Numpad3, "Num3", 0x63, 0xFFB3, 81;
+        Numpad4, "Num4", 0x64, 0xFFB4, 75;
+        Numpad5, "Num5", 0x65, 0xFFB5, 76;
+        Numpad6, "Num6", 0x66, 0xFFB6, 77;
+        Numpad7, "Num7", 0x67, 0xFFB7, 71;
+        Numpad8, "Num8", 0x68, 0xFFB8, 72;
+        Numpad9, "Num9", 0x69, 0xFFB9, 73;
+        NumpadMultiply, "NumMultiply", 0x6A, 0xFFAA, 55;
+        NumpadAdd, "NumAdd", 0x6B, 0xFFAB, 78;
+        NumpadSubtract, "NumSubtract", 0x6D, 0xFFAD, 74;
+        NumpadDecimal, "NumDecimal", 0x6E, 0xFFAE, 83;
+        NumpadDivide, "NumDivide", 0x6F, 0xFFAF, 98;
+        Minus, "Minus", 0xBD, 0x2D, 12;
+        Equal, "Equal", 0xBB, 0x3D, 13;
+        LeftBracket, "LeftBracket", 0xDB, 0x5B, 26;
+        RightBracket, "RightBracket", 0xDD, 0x5D, 27;
+        Backslash, "Backslash", 0xDC, 0x5C, 43;
+        Semicolon, "Semicolon", 0xBA, 0x3B, 39;
+        Quote, "Quote", 0xDE, 0x27, 40;
+        Backquote, "Backquote", 0xC0, 0x60, 41;
+        Comma, "Comma", 0xBC, 0x2C, 51;
+        Period, "Period", 0xBE, 0x2E, 52;
+        Slash, "Slash", 0xBF, 0x2F, 53;
+        Escape, "Escape", 0x1B, 0xFF1B, 1;
+        Tab, "Tab", 0x09, 0xFF09, 15;
+        Space, "Space", 0x20, 0x20, 57;
+        Enter, "Enter", 0x0D, 0xFF0D, 28;
+        Backspace, "Backspace", 0x08, 0xFF08, 14;
+        Insert, "Insert", 0x2D, 0xFF63, 110;
+        Delete, "Delete", 0x2E, 0xFFFF, 111;
+        Home, "Home", 0x24, 0xFF50, 102;
+        End, "End", 0x23, 0xFF57, 107;
+        PageUp, "PageUp", 0x21, 0xFF55, 104;
+        PageDown, "PageDown", 0x22, 0xFF56, 109;
+        Left, "Left", 0x25, 0xFF51, 105;
+        Up, "Up", 0x26, 0xFF52, 103;
+        Right, "Right", 0x27, 0xFF53, 106;
+        Down, "Down", 0x28, 0xFF54, 108;
+        PrintScreen, "PrintScreen", 0x2C, 0xFF61, 99;
+        ScrollLock, "ScrollLock", 0x91, 0xFF14, 70;
+        Pause, "Pause", 0x13, 0xFF13, 119;
+        CapsLock, "CapsLock", 0x14, 0xFFE5, 58;
+        NumLock, "NumLock", 0x90, 0xFF7F, 69;
+        Menu, "Menu", 0x5D, 0xFF67, 127;
+        LeftCtrl, "LeftCtrl", 0xA2, 0xFFE3, 29;
+        RightCtrl, "RightCtrl", 0xA3, 0xFFE4, 97;
+        LeftShift, "LeftShift", 0xA0, 0xFFE1, 42;
+        RightShift, "RightShift", 0xA1, 0xFFE2, 54;
+        LeftAlt, "LeftAlt", 0xA4, 0xFFE9, 56;
+        RightAlt, "RightAlt", 0xA5, 0xFFEA, 100;
+        LeftSuper, "LeftSuper", 0x5B, 0xFFEB, 125;
+        RightSuper, "RightSuper", 0x5C, 0xFFEC, 126;
+        VolumeMute, "VolumeMute", 0xAD, 0x1008FF12, 113;
+        VolumeDown, "VolumeDown", 0xAE, 0x1008FF11, 114;
+        VolumeUp, "VolumeUp", 0xAF, 0x1008FF13, 115;
+        MediaNext, "MediaNext", 0xB0, 0x1008FF17, 163;
+        MediaPrevious, "MediaPrevious", 0xB1, 0x1008FF16, 165;
+        MediaStop, "MediaStop", 0xB2, 0x1008FF15, 166;
+        MediaPlayPause, "MediaPlayPause", 0xB3, 0x1008FF14, 164;
+    }
+    Mouse {
+        MouseMiddle, "MouseMiddle", 0x04, 0x112;
+        MouseBack, "MouseBack", 0x05, 0x113;
+        MouseForward, "MouseForward", 0x06, 0x114;
+    }
+    // Named by position, since the letters differ between brands. The codes are `VK_GAMEPAD_*` and `BTN_*`.
+    Gamepad {
+        PadSouth, "PadSouth", 0xC3, 0x130;
+        PadEast, "PadEast", 0xC4, 0x131;
+        PadWest, "PadWest", 0xC5, 0x134;
+        PadNorth, "PadNorth", 0xC6, 0x133;
+        PadRightBumper, "PadRB", 0xC7, 0x137;
+        PadLeftBumper, "PadLB", 0xC8, 0x136;
+        PadStart, "PadStart", 0xCF, 0x13B;
+        PadSelect, "PadSelect", 0xD0, 0x13A;
+        PadLeftStick, "PadLS", 0xD1, 0x13D;
+        PadRightStick, "PadRS", 0xD2, 0x13E;
+    }
+}
+
+impl Key {
+    /// The function key `F<n>`, for `n` in 1-24.
+    pub fn function(n: u8) -> Option<Key> {
+        match n {
+            1..=24 => Key::from_vk(0x70 + u32::from(n) - 1),
+            _ => None,
+        }
+    }
+
+    /// The key that types `c` without Shift on a US layout, e.g. `a` → A and `[` → LeftBracket.
+    pub fn from_char(c: char) -> Option<Key> {
+        match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9') => Key::from_keysym(c as u32),
+            '-' => Some(Key::Minus),
+            '=' => Some(Key::Equal),
+            '[' => Some(Key::LeftBracket),
+            ']' => Some(Key::RightBracket),
+            '\\' => Some(Key::Backslash),
+            ';' => Some(Key::Semicolon),
+            '\'' => Some(Key::Quote),
+            '`' => Some(Key::Backquote),
+            ',' => Some(Key::Comma),
+            '.' => Some(Key::Period),
+            '/' => Some(Key::Slash),
+            ' ' => Some(Key::Space),
+            _ => None,
+        }
+    }
+
+    /// Names that are accepted on top of the canonical ones.
+    fn from_alias(name: &str) -> Option<Key> {
+        Some(match name {
+            "esc" => Key::Escape,
+            "return" => Key::Enter,
+            "del" => Key::Delete,
+            "ins" => Key::Insert,
+            "pgup" | "prior" => Key::PageUp,
+            "pgdn" | "next" => Key::PageDown,
+            "print" | "prtsc" | "sysrq" => Key::PrintScreen,
+            "apps" | "contextmenu" => Key::Menu,
+            "grave" => Key::Backquote,
+            "apostrophe" => Key::Quote,
+            "dot" => Key::Period,
+            "plus" | "add" | "kpadd" => Key::NumpadAdd,
+            "mouse3" | "middleclick" => Key::MouseMiddle,
+            "mouse4" | "xbutton1" => Key::MouseBack,
+            "mouse5" | "xbutton2" => Key::MouseForward,
+            // Xbox letters.
+            "pada" => Key::PadSouth,
+            "padb" => Key::PadEast,
+            "padx" => Key::PadWest,
+            "pady" => Key::PadNorth,
+            "padback" | "padview" => Key::PadSelect,
+            "padmenu" => Key::PadStart,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parses a canonical name (case-insensitive), an alias like `Esc`, a single character such as `[`,
+/// or a raw Windows virtual-key code like `0x78`.
+impl FromStr for Key {
+    type Err = eyre::Report;
+
+    fn from_str(name: &str) -> eyre::Result<Key> {
+        let name = name.trim();
+        if let Some(hex) = name.strip_prefix("0x").or_else(|| name.strip_prefix("0X")) {
+            let vk = u32::from_str_radix(hex, 16)?;
+            return Key::from_vk(vk).ok_or_else(|| {
+                eyre::eyre!("{:#X} is not a virtual-key code cursor-lock knows", vk)
+            });
+        }
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            if let Some(key) = Key::from_char(c) {
+                return Ok(key);
+            }
+        }
+        let lower = name.to_ascii_lowercase();
+        Key::ALL
+            .iter()
+            .copied()
+            .find(|key| key.name().eq_ignore_ascii_case(&lower))
+            .or_else(|| Key::from_alias(&lower))
+            .ok_or_else(|| {
+                eyre::eyre!(
+                    "Unrecognised key {:?}; expected a letter, a digit, F1-F24, a name like PageUp, Num5, MouseBack or PadA, or a virtual-key code like 0x78",
+                    name
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_key_round_trips_through_every_platform_code() {
+        for &key in Key::ALL {
+            assert_eq!(Key::from_vk(key.to_vk()), Some(key), "vk of {}", key);
+            if key.kind() == KeyKind::Keyboard {
+                assert_eq!(
+                    Key::from_keysym(key.to_keysym()),
+                    Some(key),
+                    "keysym of {}",
+                    key
+                );
+            } else {
+                assert_eq!(key.to_keysym(), 0, "keysym of {}", key);
+            }
+            assert_eq!(
+                Key::from_evdev(key.to_evdev()),
+                Some(key),
+                "evdev of {}",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn every_key_round_trips_through_its_name() {
+        for &key in Key::ALL {
+            assert_eq!(key.name().parse::<Key>().unwrap(), key);
+            assert_eq!(key.name().to_lowercase().parse::<Key>().unwrap(), key);
+            assert_eq!(format!("{:#x}", key.to_vk()).parse::<Key>().unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn shifted_letter_keysyms_map_to_the_same_key() {
+        assert_eq!(Key::from_keysym(0x4C), Some(Key::L));
+    }
+
+    #[test]
+    fn characters_and_aliases_parse() {
+        assert_eq!("[".parse::<Key>().unwrap(), Key::LeftBracket);
+        assert_eq!("l".parse::<Key>().unwrap(), Key::L);
+        assert_eq!("Esc".parse::<Key>().unwrap(), Key::Escape);
+        assert_eq!("Mouse4".parse::<Key>().unwrap(), Key::MouseBack);
+        assert_eq!("PadA".parse::<Key>().unwrap(), Key::PadSouth);
+        assert_eq!(Key::function(9), Some(Key::F9));
+        assert_eq!(Key::function(25), None);
+        assert!("é".parse::<Key>().is_err());
+        assert!("0xFF".parse::<Key>().is_err());
+    }
+}
@@ -1,73 +1,274 @@
 mod chimes;
+mod cli;
 mod clip_cursor;
+mod config;
+mod controller;
+mod dpi;
 mod focus;
 mod hotkeys;
+mod ipc;
+mod key;
 mod monitors;
+mod rect;
+#[cfg(target_os = "linux")]
+mod session;
 mod toggle_key;
+mod tones;
 
-use clip_cursor::{activate_clipping, deactivate_clipping};
+use chimes::ChimePlayer;
+use chimes::RenderedChimes;
+use chimes::WavOutput;
+use clap::CommandFactory;
+use clap::Parser;
+use cli::Cli;
+use cli::Command;
+use cli::RunArgs;
+use config::Config;
+use config::KeyMode;
+use config::ReapplyPolicy;
+use controller::Command as ControllerCommand;
+use controller::Controller;
 use eyre::bail;
+use hotkeys::sequence::Step;
+use hotkeys::Binding;
+use hotkeys::HotkeyAction;
+use ipc::Request;
+use ipc::Response;
 use monitors::pick_monitor;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::thread;
-use std::time::Duration;
+use monitors::MonitorSelector;
+use std::io::IsTerminal;
+use std::process::ExitCode;
+use toggle_key::parse_toggle_key;
 use toggle_key::pick_toggle_key;
-use windows::Win32::Foundation::RECT;
+use toggle_key::Chord;
+use toggle_key::Modifiers;
 
-fn main() -> eyre::Result<()> {
+fn main() -> eyre::Result<ExitCode> {
     color_eyre::install()?;
 
-    // Ask the user to pick a monitor.
-    let monitor = match pick_monitor() {
-        Some(m) => m,
+    let cli = Cli::parse();
+    match cli.command.unwrap_or(Command::Run(RunArgs::default())) {
+        Command::List => list(),
+        Command::Run(args) => run(args, Config::load(cli.config.as_deref())?),
+        Command::Lock => request(Request::Lock),
+        Command::Unlock => request(Request::Unlock),
+        Command::Status => request(Request::Status),
+    }
+}
+
+/// Exits with a usage error when a required answer is missing and we can't prompt for it.
+fn require_terminal(argument: &str) {
+    if !std::io::stdin().is_terminal() {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                format!("{} is required when stdin is not a terminal", argument),
+            )
+            .exit();
+    }
+}
+
+fn list() -> eyre::Result<ExitCode> {
+    let monitors = monitors::get_monitors();
+    if monitors.is_empty() {
+        bail!("No monitors found.");
+    }
+    monitors::print_monitors(&monitors);
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Forwards a request to the running instance and reports its answer.
+fn request(request: Request) -> eyre::Result<ExitCode> {
+    match ipc::send(request)? {
+        None => {
+            eprintln!("cursor-lock is not running; start it with `cursor-lock run`.");
+            Ok(ExitCode::from(cli::EXIT_NOT_RUNNING))
+        }
+        Some(Response::Locked) => {
+            println!("locked");
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(Response::Unlocked) => {
+            println!("unlocked");
+            if request == Request::Status {
+                Ok(ExitCode::from(cli::EXIT_UNLOCKED))
+            } else {
+                Ok(ExitCode::SUCCESS)
+            }
+        }
+        Some(Response::Error(message)) => bail!("The running instance failed: {}", message),
+    }
+}
+
+fn run(args: RunArgs, config: Config) -> eyre::Result<ExitCode> {
+    chimes::set_enabled(config.sounds.enabled);
+    let sound_pack = config.sounds.sound_pack()?;
+
+    // Command-line arguments win over the config file, which wins over prompting.
+    let selector = args.monitor.map(MonitorSelector::new).or(config.monitor);
+    let monitors = monitors::get_monitors();
+    let target = match &selector {
+        Some(selector) => selector.select(&monitors)?,
         None => {
-            bail!("No monitor selected.");
+            require_terminal("--monitor");
+            // Ask the user to pick a monitor.
+            match pick_monitor(&monitors) {
+                Some(index) => index,
+                None => {
+                    bail!("No monitor selected.");
+                }
+            }
         }
     };
+    let monitor = &monitors[target];
 
     // Compute the rectangle for cursor clipping.
-    let rect = RECT {
-        left: monitor.x,
-        top: monitor.y,
-        right: monitor.x + monitor.width,
-        bottom: monitor.y + monitor.height,
-    };
+    let confinement = clip_cursor::default_backend()?;
+    let rect = dpi::clip_rect(monitor, confinement.coordinate_space());
 
     println!(
         "Locking cursor to monitor: {} ({}x{}, pos: {}x{})",
-        monitor.name, monitor.width, monitor.height, monitor.x, monitor.y
+        monitor.name,
+        rect.width(),
+        rect.height(),
+        rect.left,
+        rect.top
     );
 
-    // Wait for the user to press a key for the toggle key.
-    let toggle_key = pick_toggle_key().unwrap_or(0x78); // Default to F9 (0x78) if something goes wrong.
+    let toggle_key = match (&args.key, config.key) {
+        (Some(key), _) => parse_toggle_key(key)?,
+        (None, Some(key)) => key,
+        (None, None) => {
+            require_terminal("--key");
+            // Wait for the user to press a key for the toggle key.
+            pick_toggle_key()?
+        }
+    };
 
-    // Now activate clipping.
-    activate_clipping(rect)?;
-    // The global "enabled" state starts as true.
-    let enabled = Arc::new(AtomicBool::new(true));
+    let action = match args.mode.or(config.mode).unwrap_or_default() {
+        KeyMode::Toggle => HotkeyAction::Toggle,
+        KeyMode::HoldToUnlock => HotkeyAction::HoldToUnlock,
+        KeyMode::HoldToLock => HotkeyAction::HoldToLock,
+        KeyMode::Gestures => HotkeyAction::Gestures(config.gestures.gestures()),
+    };
+    let optional_key = |arg: Option<String>, configured: Option<Chord>| {
+        arg.map(|key| parse_toggle_key(&key))
+            .transpose()
+            .map(|key| key.or(configured))
+    };
+    let mut bindings = vec![Binding {
+        key: toggle_key,
+        fallback: optional_key(args.fallback_key, config.hotkeys.fallback)?,
+        action,
+    }];
+    if let Some(key) = optional_key(args.next_key, config.hotkeys.next)? {
+        bindings.push(Binding {
+            key,
+            fallback: None,
+            action: HotkeyAction::NextMonitor,
+        });
+    }
+    if let Some(key) = optional_key(args.previous_key, config.hotkeys.previous)? {
+        bindings.push(Binding {
+            key,
+            fallback: None,
+            action: HotkeyAction::PreviousMonitor,
+        });
+    }
+    if let Some(key) = optional_key(args.mute_key, config.hotkeys.mute)? {
+        bindings.push(Binding {
+            key,
+            fallback: None,
+            action: HotkeyAction::ToggleMute,
+        });
+    }
+    if let Some(key) = optional_key(args.leader_key, config.hotkeys.leader)? {
+        bindings.push(Binding {
+            key,
+            fallback: None,
+            action: HotkeyAction::Leader(config.hotkeys.leader_timeout()),
+        });
+        for (key, step) in Step::all() {
+            bindings.push(Binding {
+                key: Chord {
+                    modifiers: Modifiers::NONE,
+                    key,
+                },
+                fallback: None,
+                action: HotkeyAction::AfterLeader(step),
+            });
+        }
+    }
+    let monitor_keys = if args.monitor_keys.is_empty() {
+        config.hotkeys.monitors
+    } else {
+        args.monitor_keys
+            .iter()
+            .map(|key| parse_toggle_key(key))
+            .collect::<eyre::Result<_>>()?
+    };
+    for (index, key) in monitor_keys.into_iter().enumerate() {
+        bindings.push(Binding {
+            key,
+            fallback: None,
+            action: HotkeyAction::LockToMonitor(index),
+        });
+    }
 
-    // Launch the hotkey listener in a separate thread using the chosen key.
-    hotkeys::run_hotkey_listener(rect, enabled.clone(), toggle_key)?;
-    // Launch the focus hook to reapply clipping on foreground changes.
-    focus::run_focus_hook(rect, enabled.clone())?;
+    // The controller owns the lock; everything below only sends it commands.
+    let chimes: Box<dyn ChimePlayer> = match &args.chimes_wav {
+        Some(path) => Box::new(RenderedChimes::new(sound_pack, WavOutput::create(path)?)),
+        None => Box::new(chimes::RodioChimes::spawn(sound_pack)),
+    };
+    let mut controller = Controller::new(confinement, chimes, monitors, target);
+    #[cfg(target_os = "linux")]
+    if !config.sounds.quiet_apps.is_empty() && session::detect() == session::LinuxSession::Wayland {
+        eprintln!(
+            "Warning: `quiet_apps` has no effect on Wayland, which doesn't say which app is focused."
+        );
+    }
+    controller.set_quiet_apps(config.sounds.quiet_apps.clone());
+    let handle = controller.handle();
 
-    // Install a Ctrl+C handler to ensure clipping is deactivated on exit.
+    // Answer `cursor-lock lock|unlock|status`. This also fails early if another instance is running.
     {
-        let enabled_clone = enabled.clone();
-        ctrlc::set_handler(move || {
-            if enabled_clone.load(Ordering::SeqCst) {
-                if let Err(e) = deactivate_clipping() {
-                    eprintln!("Error deactivating clipping: {:?}", e);
-                }
+        let handle = handle.clone();
+        ipc::serve(move |request| {
+            let command = match request {
+                Request::Lock => ControllerCommand::Lock,
+                Request::Unlock => ControllerCommand::Unlock,
+                Request::Status => ControllerCommand::Status,
+            };
+            match handle.request(command) {
+                Err(e) => Response::Error(e.to_string()),
+                Ok(true) => Response::Locked,
+                Ok(false) => Response::Unlocked,
             }
-            std::process::exit(0);
         })?;
     }
 
-    println!("Hotkey listener running (press your chosen key to toggle clipping). Press Ctrl+C to exit.");
-    // Wait indefinitely.
-    loop {
-        thread::sleep(Duration::from_secs(1));
+    // Now activate clipping.
+    controller.handle_command(ControllerCommand::Lock)?;
+
+    // Launch the hotkey listener in a separate thread using the chosen key.
+    hotkeys::run_hotkey_listener(handle.clone(), bindings)?;
+    // Launch the focus hook to reapply clipping on foreground changes and notice quiet apps.
+    let reapply_delay =
+        (config.reapply.policy != ReapplyPolicy::Never).then(|| config.focus.delay());
+    if reapply_delay.is_some() || !config.sounds.quiet_apps.is_empty() {
+        focus::run_focus_hook(handle.clone(), reapply_delay)?;
+    }
+    if config.reapply.policy == ReapplyPolicy::Interval {
+        focus::run_periodic_reapply(handle.clone(), config.reapply.interval());
     }
+
+    // Install a Ctrl+C handler to ensure clipping is deactivated on exit.
+    ctrlc::set_handler(move || handle.send(ControllerCommand::Shutdown))?;
+
+    println!(
+        "Hotkey listener running (press your chosen key to toggle clipping). Press Ctrl+C to exit."
+    );
+    // Handle commands until Ctrl+C.
+    controller.run();
+    Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,119 @@
+/// The fixed 8-byte header every EDID base block starts with.
+const EDID_HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+/// Offsets of the four 18-byte descriptors in the EDID base block.
+const DESCRIPTOR_OFFSETS: [usize; 4] = [54, 72, 90, 108];
+/// Descriptor tag for the "display product name" string.
+const TAG_PRODUCT_NAME: u8 = 0xFC;
+/// Descriptor tag for the "display product serial number" string.
+const TAG_SERIAL: u8 = 0xFF;
+
+/// Returns the EDID base block, if `edid` starts with one.
+#[cfg_attr(windows, allow(dead_code))]
+fn base_block(edid: &[u8]) -> Option<&[u8]> {
+    (edid.len() >= 128 && edid[..8] == EDID_HEADER).then(|| &edid[..128])
+}
+
+/// Returns the text of the display descriptor tagged `tag`.
+#[cfg_attr(windows, allow(dead_code))]
+fn descriptor_text(edid: &[u8], tag: u8) -> Option<String> {
+    let edid = base_block(edid)?;
+    DESCRIPTOR_OFFSETS.iter().find_map(|&offset| {
+        let descriptor = &edid[offset..offset + 18];
+        // Display descriptors start with a zero pixel clock; timing descriptors don't.
+        if descriptor[..3] != [0, 0, 0] || descriptor[3] != tag {
+            return None;
+        }
+        // The text is up to 13 bytes, terminated by a newline and padded with spaces.
+        let text = &descriptor[5..];
+        let end = text.iter().position(|&b| b == b'\n').unwrap_or(text.len());
+        let text = String::from_utf8_lossy(&text[..end]).trim().to_string();
+        (!text.is_empty()).then_some(text)
+    })
+}
+
+/// Extracts the monitor's product name (e.g. "MAG274UPF") from an EDID blob.
+#[cfg_attr(windows, allow(dead_code))]
+pub fn product_name(edid: &[u8]) -> Option<String> {
+    descriptor_text(edid, TAG_PRODUCT_NAME)
+}
+
+/// Builds a hardware id like `MSI3CA9-0000010A` from an EDID blob: the PNP manufacturer code,
+/// the product code and, when the monitor reports one, its serial number.
+#[cfg_attr(windows, allow(dead_code))]
+pub fn identity(edid: &[u8]) -> Option<String> {
+    let edid = base_block(edid)?;
+    let manufacturer = u16::from_be_bytes([edid[8], edid[9]]);
+    let product = u16::from_le_bytes([edid[10], edid[11]]);
+    // Prefer the serial string; many monitors leave the numeric one zeroed or set to a constant.
+    let serial = descriptor_text(edid, TAG_SERIAL).or_else(|| {
+        let serial = u32::from_le_bytes([edid[12], edid[13], edid[14], edid[15]]);
+        (serial != 0).then(|| format!("{:08X}", serial))
+    });
+    hardware_id(manufacturer, product, serial)
+}
+
+/// Formats a hardware id from EDID fields. `manufacturer` is the big-endian value from bytes 8-9.
+pub fn hardware_id(manufacturer: u16, product: u16, serial: Option<String>) -> Option<String> {
+    // Three letters packed as 5-bit values, 'A' = 1.
+    let code: Option<String> = [10, 5, 0]
+        .iter()
+        .map(|shift| match (manufacturer >> shift) & 0x1F {
+            letter @ 1..=26 => Some(char::from(b'A' + letter as u8 - 1)),
+            _ => None,
+        })
+        .collect();
+    let id = format!("{}{:04X}", code?, product);
+    Some(match serial {
+        Some(serial) => format!("{}-{}", id, serial),
+        None => id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An EDID base block for an MSI MAG274UPF with numeric serial `serial`.
+    fn edid(serial: u32) -> Vec<u8> {
+        let mut edid = vec![0u8; 128];
+        edid[..8].copy_from_slice(&EDID_HEADER);
+        // "MSI": M = 13, S = 19, I = 9, five bits each.
+        edid[8..10].copy_from_slice(&((13u16 << 10) | (19 << 5) | 9).to_be_bytes());
+        edid[10..12].copy_from_slice(&0x3CA9u16.to_le_bytes());
+        edid[12..16].copy_from_slice(&serial.to_le_bytes());
+        // A timing descriptor first, which must be skipped.
+        edid[54] = 0x01;
+        descriptor(&mut edid, 72, TAG_PRODUCT_NAME, b"MAG274UPF\n   ");
+        edid
+    }
+
+    fn descriptor(edid: &mut [u8], offset: usize, tag: u8, text: &[u8; 13]) {
+        edid[offset..offset + 5].copy_from_slice(&[0, 0, 0, tag, 0]);
+        edid[offset + 5..offset + 18].copy_from_slice(text);
+    }
+
+    #[test]
+    fn identity_comes_from_manufacturer_product_and_serial() {
+        let edid = edid(0x010A);
+        assert_eq!(product_name(&edid).as_deref(), Some("MAG274UPF"));
+        assert_eq!(identity(&edid).as_deref(), Some("MSI3CA9-0000010A"));
+    }
+
+    #[test]
+    fn zero_serial_is_left_out_and_the_serial_string_wins() {
+        let mut edid = edid(0);
+        assert_eq!(identity(&edid).as_deref(), Some("MSI3CA9"));
+        descriptor(&mut edid, 90, TAG_SERIAL, b"AB12CD\n      ");
+        assert_eq!(identity(&edid).as_deref(), Some("MSI3CA9-AB12CD"));
+    }
+
+    #[test]
+    fn blobs_without_an_edid_header_are_ignored() {
+        let mut edid = edid(1);
+        edid[0] = 0xFF;
+        assert_eq!(identity(&edid), None);
+        assert_eq!(product_name(&edid[..64]), None);
+        // Letters outside A-Z make the manufacturer code invalid.
+        assert_eq!(hardware_id(0, 0x3CA9, None), None);
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+
+/// Identifies a monitor independently of where it sits in the desktop layout,
+/// so that a saved selector keeps pointing at the same screen after re-arranging displays.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MonitorId {
+    /// The display itself, e.g. `MSI3CA9-0000010A` from its EDID manufacturer, product and serial.
+    /// Wayland doesn't expose EDID, so there it is the make and model the compositor reports.
+    pub hardware: Option<String>,
+    /// Where it is plugged in, e.g. `DP-1`.
+    pub connector: Option<String>,
+}
+
+impl MonitorId {
+    /// The strings a selector can match exactly: the full id, the hardware id and the connector.
+    pub fn aliases(&self) -> impl Iterator<Item = String> + '_ {
+        [
+            Some(self.to_string()),
+            self.hardware.clone(),
+            self.connector.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|alias| !alias.is_empty())
+    }
+}
+
+/// Formats as `hardware@connector`, which tells two identical models apart by where they are plugged in.
+impl fmt::Display for MonitorId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.hardware, &self.connector) {
+            (Some(hardware), Some(connector)) => write!(f, "{}@{}", hardware, connector),
+            (Some(id), None) | (None, Some(id)) => f.write_str(id),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_join_hardware_and_connector() {
+        let id = MonitorId {
+            hardware: Some("MSI3CA9-0000010A".to_string()),
+            connector: Some("DP-1".to_string()),
+        };
+        assert_eq!(id.to_string(), "MSI3CA9-0000010A@DP-1");
+        assert_eq!(
+            id.aliases().collect::<Vec<_>>(),
+            ["MSI3CA9-0000010A@DP-1", "MSI3CA9-0000010A", "DP-1"]
+        );
+        let connector_only = MonitorId {
+            hardware: None,
+            connector: Some("DP-1".to_string()),
+        };
+        assert_eq!(connector_only.to_string(), "DP-1");
+        assert_eq!(MonitorId::default().aliases().count(), 0);
+    }
+}
@@ -0,0 +1,142 @@
+mod edid;
+mod id;
+mod selector;
+#[cfg(target_os = "linux")]
+mod wayland;
+#[cfg(windows)]
+mod win32;
+#[cfg(windows)]
+pub use win32::enable_per_monitor_dpi_awareness;
+#[cfg(target_os = "linux")]
+mod x11;
+
+use crate::rect::Rect;
+use eyre::Result;
+pub use id::MonitorId;
+pub use selector::MonitorSelector;
+use std::io::Write;
+use std::io::{self};
+
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    pub name: String,
+    /// A longer human-readable description, when the platform provides one.
+    pub description: Option<String>,
+    /// Stays the same across reboots and layout changes, unlike the position in the list.
+    pub id: MonitorId,
+    /// The area covered in device pixels.
+    pub physical: Rect,
+    /// The area covered in scaled units, see [`crate::dpi::physical_to_logical`].
+    pub logical: Rect,
+    /// The scale factor applied to this monitor (1.0 = 100%).
+    pub scale: f64,
+}
+
+/// Enumerates the monitors currently attached to the desktop.
+pub trait DisplayBackend {
+    fn get_monitors(&self) -> Result<Vec<Monitor>>;
+}
+
+/// Returns the display backend for the platform we were built for.
+#[cfg(windows)]
+pub fn default_backend() -> Result<Box<dyn DisplayBackend>> {
+    Ok(Box::new(win32::Win32Displays::new()))
+}
+
+/// Returns the display backend for the platform we were built for.
+#[cfg(target_os = "linux")]
+pub fn default_backend() -> Result<Box<dyn DisplayBackend>> {
+    match crate::session::detect() {
+        crate::session::LinuxSession::Wayland => Ok(Box::new(wayland::WaylandDisplays::connect()?)),
+        crate::session::LinuxSession::X11 => Ok(Box::new(x11::X11Displays::connect()?)),
+    }
+}
+
+pub fn get_monitors() -> Vec<Monitor> {
+    let mut monitors = match default_backend().and_then(|backend| backend.get_monitors()) {
+        Ok(monitors) => monitors,
+        Err(e) => {
+            eprintln!("Failed to enumerate monitors: {:?}", e);
+            return Vec::new();
+        }
+    };
+
+    // Sort monitors: first left-to-right (by x) then top-to-bottom (by y)
+    monitors.sort_by(|a, b| {
+        if a.physical.left == b.physical.left {
+            a.physical.top.cmp(&b.physical.top)
+        } else {
+            a.physical.left.cmp(&b.physical.left)
+        }
+    });
+
+    monitors
+}
+
+/// Prints the numbered monitor list that both the prompt and `cursor-lock list` show.
+pub fn print_monitors(monitors: &[Monitor]) {
+    for (i, monitor) in monitors.iter().enumerate() {
+        println!(
+            "{}: {} ({}x{}, pos: {}x{})",
+            i + 1,
+            monitor.name,
+            monitor.physical.width(),
+            monitor.physical.height(),
+            monitor.physical.left,
+            monitor.physical.top
+        );
+        if monitor.scale != 1.0 {
+            println!(
+                "   scale: {}% (logical {}x{})",
+                monitor.scale * 100.0,
+                monitor.logical.width(),
+                monitor.logical.height()
+            );
+        }
+        if let Some(description) = &monitor.description {
+            println!("   {}", description);
+        }
+        let id = monitor.id.to_string();
+        if !id.is_empty() {
+            println!("   id: {}", id);
+        }
+    }
+}
+
+/// Prompts for one of `monitors` and returns its position in the list.
+pub fn pick_monitor(monitors: &[Monitor]) -> Option<usize> {
+    if monitors.is_empty() {
+        eprintln!("No monitors found.");
+        return None;
+    }
+
+    println!("Available monitors:");
+    print_monitors(monitors);
+
+    print!("Please select a monitor by entering its number: ");
+    io::stdout().flush().unwrap();
+
+    let mut input = String::new();
+    if let Err(err) = io::stdin().read_line(&mut input) {
+        eprintln!("Failed to read input: {}", err);
+        return None;
+    }
+
+    let trimmed = input.trim();
+    if let Ok(index) = trimmed.parse::<usize>() {
+        if index > 0 && index <= monitors.len() {
+            // Numbers follow the layout, so point at something that survives re-arranging displays.
+            let id = monitors[index - 1].id.to_string();
+            if !id.is_empty() {
+                println!(
+                    "Tip: pass `--monitor {}` to pick this monitor next time.",
+                    id
+                );
+            }
+            return Some(index - 1);
+        }
+    }
+
+    eprintln!("Invalid selection.");
+    None
+}
@@ -0,0 +1,181 @@
+use crate::monitors::Monitor;
+use eyre::Result;
+use regex::Regex;
+use regex::RegexBuilder;
+
+/// Picks one monitor out of the list printed by `cursor-lock list`.
+///
+/// The selector is tried, in order, as a 1-based index, as an exact (case-insensitive) id
+/// (see [`crate::monitors::MonitorId::aliases`]), as an exact name, and finally as a
+/// case-insensitive regular expression matched against the name, description and id. A selector
+/// that isn't a valid regex, e.g. a name with an unbalanced `(`, is matched as plain text instead.
+/// Only ids keep matching the same screen after re-arranging displays.
+#[derive(Debug, Clone)]
+pub struct MonitorSelector(String);
+
+impl MonitorSelector {
+    pub fn new(selector: impl Into<String>) -> Self {
+        Self(selector.into())
+    }
+
+    /// Returns the position of the single monitor matching the selector, or an error describing why there isn't one.
+    pub fn select(&self, monitors: &[Monitor]) -> Result<usize> {
+        let selector = self.0.trim();
+
+        if let Ok(index) = selector.parse::<usize>() {
+            return match index.checked_sub(1).filter(|&i| i < monitors.len()) {
+                Some(i) => Ok(i),
+                None => eyre::bail!(
+                    "Monitor index {} is out of range (1-{})",
+                    index,
+                    monitors.len()
+                ),
+            };
+        }
+
+        let matching = |matches: &dyn Fn(&Monitor) -> bool| -> Vec<usize> {
+            (0..monitors.len())
+                .filter(|&i| matches(&monitors[i]))
+                .collect()
+        };
+
+        let by_id = matching(&|m| {
+            m.id.aliases()
+                .any(|alias| alias.eq_ignore_ascii_case(selector))
+        });
+        match by_id[..] {
+            [i] => return Ok(i),
+            [] => {}
+            _ => return Err(ambiguous(selector, monitors, &by_id)),
+        }
+
+        if let [i] = matching(&|m| m.name.eq_ignore_ascii_case(selector))[..] {
+            return Ok(i);
+        }
+
+        let (pattern, not_a_regex) = pattern(selector)?;
+        let matches = matching(&|m| {
+            pattern.is_match(&m.name)
+                || pattern.is_match(&m.id.to_string())
+                || m.description
+                    .as_deref()
+                    .is_some_and(|description| pattern.is_match(description))
+        });
+        match matches[..] {
+            [i] => Ok(i),
+            [] => match not_a_regex {
+                Some(e) => eyre::bail!(
+                    "No monitor matches {:?}, which was taken as plain text because it isn't a valid regex: {}",
+                    selector,
+                    e
+                ),
+                None => eyre::bail!("No monitor matches {:?}", selector),
+            },
+            _ => Err(ambiguous(selector, monitors, &matches)),
+        }
+    }
+}
+
+/// Compiles `selector` as a case-insensitive regex, falling back to matching it as plain text.
+/// The regex error comes along in that case, to explain a selector that was meant as a regex.
+fn pattern(selector: &str) -> Result<(Regex, Option<regex::Error>)> {
+    let build = |pattern: &str| RegexBuilder::new(pattern).case_insensitive(true).build();
+    Ok(match build(selector) {
+        Ok(pattern) => (pattern, None),
+        Err(e) => (build(&regex::escape(selector))?, Some(e)),
+    })
+}
+
+/// Lists the candidates by id, which tells identical models apart where their names can't.
+fn ambiguous(selector: &str, monitors: &[Monitor], matches: &[usize]) -> eyre::Report {
+    let candidates = matches
+        .iter()
+        .map(|&i| &monitors[i])
+        .map(|m| match m.id.to_string() {
+            id if id.is_empty() => m.name.clone(),
+            id => format!("{} ({})", m.name, id),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    eyre::eyre!(
+        "{:?} matches {} monitors: {}; use one of the ids from `cursor-lock list` instead",
+        selector,
+        matches.len(),
+        candidates
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitors::MonitorId;
+    use crate::rect::Rect;
+
+    fn monitor(name: &str, hardware: &str, connector: &str) -> Monitor {
+        let physical = Rect::from_origin_size(0, 0, 1920, 1080);
+        Monitor {
+            name: name.to_string(),
+            description: None,
+            id: MonitorId {
+                hardware: Some(hardware.to_string()),
+                connector: Some(connector.to_string()),
+            },
+            physical,
+            logical: physical,
+            scale: 1.0,
+        }
+    }
+
+    /// Two of the same model next to a different one.
+    fn monitors() -> Vec<Monitor> {
+        vec![
+            monitor("MAG274UPF", "MSI3CA9-0000010A", "DP-1"),
+            monitor("MAG274UPF", "MSI3CA9-0000020B", "DP-2"),
+            monitor("DELL U2720Q", "DEL41A8", "HDMI-1"),
+        ]
+    }
+
+    fn select(selector: &str) -> Result<usize> {
+        MonitorSelector::new(selector).select(&monitors())
+    }
+
+    #[test]
+    fn ids_tell_identical_models_apart() {
+        assert_eq!(select("MSI3CA9-0000020B").unwrap(), 1);
+        assert_eq!(select("dp-1").unwrap(), 0);
+        assert_eq!(select("MSI3CA9-0000010A@DP-1").unwrap(), 0);
+        assert_eq!(select("dell u2720q").unwrap(), 2);
+        assert_eq!(select(" 2 ").unwrap(), 1);
+    }
+
+    #[test]
+    fn shared_names_are_ambiguous_and_list_the_ids() {
+        let error = select("MAG274UPF").unwrap_err().to_string();
+        assert!(error.contains("matches 2 monitors"), "{}", error);
+        assert!(error.contains("MSI3CA9-0000010A@DP-1"), "{}", error);
+        assert!(error.contains("MSI3CA9-0000020B@DP-2"), "{}", error);
+        // A shared hardware id without a serial is just as ambiguous.
+        let error = select("MSI3CA9").unwrap_err().to_string();
+        assert!(error.contains("matches 2 monitors"), "{}", error);
+    }
+
+    #[test]
+    fn indexes_out_of_range_are_rejected() {
+        for selector in ["0", "4"] {
+            let error = select(selector).unwrap_err().to_string();
+            assert!(error.contains("out of range (1-3)"), "{}", error);
+        }
+        assert!(select("BenQ").is_err());
+    }
+
+    #[test]
+    fn selectors_that_are_not_regexes_match_as_plain_text() {
+        let mut monitors = monitors();
+        monitors[2].name = "Projector (lobby".to_string();
+        let select = |selector: &str| MonitorSelector::new(selector).select(&monitors);
+        assert_eq!(select("Projector (lobby").unwrap(), 2);
+        assert_eq!(select("(LOBBY").unwrap(), 2);
+        let error = select("MAG[27").unwrap_err().to_string();
+        assert!(error.contains("isn't a valid regex"), "{}", error);
+    }
+}
@@ -0,0 +1,359 @@
+use crate::dpi::logical_to_physical;
+use crate::monitors::DisplayBackend;
+use crate::monitors::Monitor;
+use crate::monitors::MonitorId;
+use crate::rect::Rect;
+use eyre::Result;
+use wayland_client::protocol::wl_output;
+use wayland_client::protocol::wl_output::WlOutput;
+use wayland_client::protocol::wl_registry;
+use wayland_client::protocol::wl_registry::WlRegistry;
+use wayland_client::Connection;
+use wayland_client::Dispatch;
+use wayland_client::Proxy;
+use wayland_client::QueueHandle;
+use wayland_client::WEnum;
+use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_manager_v1::ZxdgOutputManagerV1;
+use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1;
+use wayland_protocols::xdg::xdg_output::zv1::client::zxdg_output_v1::ZxdgOutputV1;
+
+/// Everything the compositor told us about one `wl_output`.
+#[derive(Default)]
+struct OutputInfo {
+    name: Option<String>,
+    description: Option<String>,
+    make: Option<String>,
+    model: Option<String>,
+    /// Position from `wl_output.geometry`, used when xdg-output is unavailable.
+    x: i32,
+    y: i32,
+    /// Current mode in physical pixels.
+    mode_width: i32,
+    mode_height: i32,
+    scale: i32,
+    logical_position: Option<(i32, i32)>,
+    logical_size: Option<(i32, i32)>,
+}
+
+impl OutputInfo {
+    fn into_monitor(self) -> Monitor {
+        let integer_scale = self.scale.max(1);
+        // Without xdg-output the logical size is the mode divided by the integer scale.
+        let (x, y) = self.logical_position.unwrap_or((self.x, self.y));
+        let (width, height) = self.logical_size.unwrap_or((
+            self.mode_width / integer_scale,
+            self.mode_height / integer_scale,
+        ));
+        // With fractional scaling the mode/logical ratio is the real scale. Comparing the longer
+        // sides keeps this right for rotated outputs, whose logical size is already transformed.
+        let scale = if self.mode_width > 0 && width > 0 {
+            f64::from(self.mode_width.max(self.mode_height)) / f64::from(width.max(height))
+        } else {
+            f64::from(integer_scale)
+        };
+        let logical = Rect::from_origin_size(x, y, width, height);
+        // Compositors don't share EDID, so make and model is as close to a hardware id as we get.
+        let hardware = match (&self.make, &self.model) {
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model).trim().to_string()),
+            (None, model) => model.clone(),
+            (make, None) => make.clone(),
+        }
+        .filter(|hardware| !hardware.is_empty());
+        Monitor {
+            id: MonitorId {
+                hardware,
+                connector: self.name.clone(),
+            },
+            name: self
+                .name
+                .or(self.model)
+                .unwrap_or_else(|| "Unknown".to_string()),
+            description: self.description,
+            physical: logical_to_physical(logical, scale),
+            logical,
+            scale,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    outputs: Vec<(WlOutput, OutputInfo)>,
+    xdg_output_manager: Option<ZxdgOutputManagerV1>,
+}
+
+/// Enumerates outputs on a Wayland compositor using `wl_output` and `zxdg_output_manager_v1`.
+pub struct WaylandDisplays {
+    conn: Connection,
+}
+
+impl WaylandDisplays {
+    /// Connects to the compositor named by `$WAYLAND_DISPLAY`.
+    pub fn connect() -> Result<Self> {
+        Ok(Self {
+            conn: Connection::connect_to_env()?,
+        })
+    }
+}
+
+impl DisplayBackend for WaylandDisplays {
+    fn get_monitors(&self) -> Result<Vec<Monitor>> {
+        let mut queue = self.conn.new_event_queue();
+        let qh = queue.handle();
+        let _registry = self.conn.display().get_registry(&qh, ());
+        let mut state = State::default();
+
+        // First roundtrip binds the globals, the second delivers the output properties.
+        queue.roundtrip(&mut state)?;
+        let mut xdg_outputs = Vec::new();
+        if let Some(manager) = &state.xdg_output_manager {
+            for (index, (output, _)) in state.outputs.iter().enumerate() {
+                xdg_outputs.push(manager.get_xdg_output(output, &qh, index));
+            }
+        }
+        queue.roundtrip(&mut state)?;
+
+        for xdg_output in xdg_outputs {
+            xdg_output.destroy();
+        }
+        if let Some(manager) = state.xdg_output_manager.take() {
+            manager.destroy();
+        }
+
+        Ok(state
+            .outputs
+            .into_iter()
+            .map(|(output, info)| {
+                if output.version() >= 3 {
+                    output.release();
+                }
+                info.into_monitor()
+            })
+            .collect())
+    }
+}
+
+impl Dispatch<WlRegistry, ()> for State {
+    fn event(
+        state: &mut Self,
+        registry: &WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } = event
+        {
+            match interface.as_str() {
+                "wl_output" => {
+                    let index = state.outputs.len();
+                    let output = registry.bind::<WlOutput, _, _>(name, version.min(4), qh, index);
+                    state.outputs.push((output, OutputInfo::default()));
+                }
+                "zxdg_output_manager_v1" => {
+                    state.xdg_output_manager = Some(registry.bind::<ZxdgOutputManagerV1, _, _>(
+                        name,
+                        version.min(3),
+                        qh,
+                        (),
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Dispatch<WlOutput, usize> for State {
+    fn event(
+        state: &mut Self,
+        _: &WlOutput,
+        event: wl_output::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let info = &mut state.outputs[*index].1;
+        match event {
+            wl_output::Event::Geometry {
+                x, y, make, model, ..
+            } => {
+                info.x = x;
+                info.y = y;
+                info.make = Some(make);
+                info.model = Some(model);
+            }
+            wl_output::Event::Mode {
+                flags: WEnum::Value(flags),
+                width,
+                height,
+                ..
+            } if flags.contains(wl_output::Mode::Current) => {
+                info.mode_width = width;
+                info.mode_height = height;
+            }
+            wl_output::Event::Scale { factor } => info.scale = factor,
+            wl_output::Event::Name { name } => info.name = Some(name),
+            wl_output::Event::Description { description } => info.description = Some(description),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZxdgOutputManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ZxdgOutputManagerV1,
+        _: <ZxdgOutputManagerV1 as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        // The manager has no events.
+    }
+}
+
+impl Dispatch<ZxdgOutputV1, usize> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZxdgOutputV1,
+        event: zxdg_output_v1::Event,
+        index: &usize,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let info = &mut state.outputs[*index].1;
+        match event {
+            zxdg_output_v1::Event::LogicalPosition { x, y } => info.logical_position = Some((x, y)),
+            zxdg_output_v1::Event::LogicalSize { width, height } => {
+                info.logical_size = Some((width, height))
+            }
+            // Older wl_output versions don't send a name or description, but xdg-output v2+ does.
+            zxdg_output_v1::Event::Name { name } => {
+                info.name.get_or_insert(name);
+            }
+            zxdg_output_v1::Event::Description { description } => {
+                info.description.get_or_insert(description);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An output as a compositor with xdg-output reports it.
+    fn output(name: &str, logical: Rect, mode: (i32, i32), scale: i32) -> OutputInfo {
+        OutputInfo {
+            name: Some(name.to_string()),
+            make: Some("MSI".to_string()),
+            model: Some(name.to_string()),
+            mode_width: mode.0,
+            mode_height: mode.1,
+            scale,
+            logical_position: Some((logical.left, logical.top)),
+            logical_size: Some((logical.width(), logical.height())),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fractional_scales_come_from_the_mode() {
+        // The README layout: 100%, 150% and 200%, the first at a negative origin.
+        let monitors: Vec<Monitor> = [
+            output(
+                "DP-1",
+                Rect::from_origin_size(-1920, 593, 1920, 1080),
+                (1920, 1080),
+                1,
+            ),
+            output(
+                "DP-2",
+                Rect::from_origin_size(0, 0, 2560, 1440),
+                (3840, 2160),
+                2,
+            ),
+            output(
+                "HDMI-A-1",
+                Rect::from_origin_size(3840, 576, 960, 540),
+                (1920, 1080),
+                2,
+            ),
+        ]
+        .into_iter()
+        .map(OutputInfo::into_monitor)
+        .collect();
+        let scales: Vec<f64> = monitors.iter().map(|m| m.scale).collect();
+        assert_eq!(scales, [1.0, 1.5, 2.0]);
+        let physical: Vec<Rect> = monitors.iter().map(|m| m.physical).collect();
+        assert_eq!(
+            physical,
+            [
+                Rect::from_origin_size(-1920, 593, 1920, 1080),
+                Rect::from_origin_size(0, 0, 3840, 2160),
+                Rect::from_origin_size(3840, 576, 1920, 1080),
+            ]
+        );
+        assert_eq!(monitors[1].id.to_string(), "MSI DP-2@DP-2");
+    }
+
+    #[test]
+    fn rotated_outputs_keep_their_scale() {
+        // The mode is untransformed, the logical size is rotated.
+        let monitor = output(
+            "DP-2",
+            Rect::from_origin_size(0, 0, 1440, 2560),
+            (3840, 2160),
+            2,
+        )
+        .into_monitor();
+        assert_eq!(monitor.scale, 1.5);
+        assert_eq!(monitor.physical, Rect::from_origin_size(0, 0, 2160, 3840));
+    }
+
+    #[test]
+    fn without_xdg_output_the_integer_scale_is_used() {
+        let info = OutputInfo {
+            model: Some("MAG274UPF".to_string()),
+            make: Some(String::new()),
+            x: 1920,
+            y: 0,
+            mode_width: 3840,
+            mode_height: 2160,
+            scale: 2,
+            ..Default::default()
+        };
+        let monitor = info.into_monitor();
+        assert_eq!(monitor.scale, 2.0);
+        assert_eq!(monitor.logical, Rect::from_origin_size(1920, 0, 1920, 1080));
+        assert_eq!(
+            monitor.physical,
+            Rect::from_origin_size(1920, 0, 3840, 2160)
+        );
+        // Without a name the model stands in, and an empty make is left out of the id.
+        assert_eq!(monitor.name, "MAG274UPF");
+        assert_eq!(monitor.id.hardware.as_deref(), Some("MAG274UPF"));
+
+        let unknown = OutputInfo::default().into_monitor();
+        assert_eq!(unknown.name, "Unknown");
+        assert_eq!(unknown.scale, 1.0);
+    }
+
+    #[test]
+    #[ignore = "needs a Wayland compositor, e.g. `weston --backend=headless-backend.so` with `WAYLAND_DISPLAY` set"]
+    fn headless_compositor_outputs_are_listed() {
+        let monitors = WaylandDisplays::connect().unwrap().get_monitors().unwrap();
+        assert!(!monitors.is_empty());
+        for monitor in monitors {
+            assert!(monitor.id.connector.is_some(), "{:?}", monitor.id);
+            assert!(monitor.physical.width() > 0 && monitor.physical.height() > 0);
+            assert!(monitor.scale >= 1.0);
+        }
+    }
+}
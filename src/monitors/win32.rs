@@ -0,0 +1,218 @@
+use crate::dpi::physical_to_logical;
+use crate::monitors::edid;
+use crate::monitors::DisplayBackend;
+use crate::monitors::Monitor;
+use crate::monitors::MonitorId;
+use crate::rect::Rect;
+use eyre::Result;
+use std::mem::size_of;
+use std::mem::zeroed;
+use std::sync::Once;
+use windows::Win32::Devices::Display::DisplayConfigGetDeviceInfo;
+use windows::Win32::Devices::Display::GetDisplayConfigBufferSizes;
+use windows::Win32::Devices::Display::QueryDisplayConfig;
+use windows::Win32::Devices::Display::DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME;
+use windows::Win32::Devices::Display::DISPLAYCONFIG_MODE_INFO;
+use windows::Win32::Devices::Display::DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE;
+use windows::Win32::Devices::Display::DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EMBEDDED;
+use windows::Win32::Devices::Display::DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EXTERNAL;
+use windows::Win32::Devices::Display::DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_USB_TUNNEL;
+use windows::Win32::Devices::Display::DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DVI;
+use windows::Win32::Devices::Display::DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HD15;
+use windows::Win32::Devices::Display::DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HDMI;
+use windows::Win32::Devices::Display::DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INTERNAL;
+use windows::Win32::Devices::Display::DISPLAYCONFIG_PATH_INFO;
+use windows::Win32::Devices::Display::DISPLAYCONFIG_TARGET_DEVICE_NAME;
+use windows::Win32::Devices::Display::QDC_ONLY_ACTIVE_PATHS;
+use windows::Win32::Foundation::ERROR_SUCCESS;
+use windows::Win32::Foundation::POINT;
+use windows::Win32::Graphics::Gdi::MonitorFromPoint;
+use windows::Win32::Graphics::Gdi::DISPLAYCONFIG_PATH_MODE_IDX_INVALID;
+use windows::Win32::Graphics::Gdi::MONITOR_DEFAULTTONEAREST;
+use windows::Win32::UI::HiDpi::GetDpiForMonitor;
+use windows::Win32::UI::HiDpi::SetProcessDpiAwarenessContext;
+use windows::Win32::UI::HiDpi::DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2;
+use windows::Win32::UI::HiDpi::MDT_EFFECTIVE_DPI;
+
+/// The DPI Windows treats as 100% scaling.
+const BASE_DPI: f64 = 96.0;
+
+/// Opts the process into per-monitor DPI awareness so that `GetDpiForMonitor` reports each
+/// monitor's real scale and `ClipCursor` takes device pixels instead of virtualized ones.
+/// This may fail if a manifest already chose an awareness; callers query the effective one.
+pub fn enable_per_monitor_dpi_awareness() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| unsafe {
+        let _ = SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+    });
+}
+
+/// Returns the effective scale of the monitor containing the centre of `physical`.
+fn scale_for(physical: Rect) -> f64 {
+    let center = POINT {
+        x: physical.left + physical.width() / 2,
+        y: physical.top + physical.height() / 2,
+    };
+    let (mut dpi_x, mut dpi_y) = (0, 0);
+    let result = unsafe {
+        let hmonitor = MonitorFromPoint(center, MONITOR_DEFAULTTONEAREST);
+        GetDpiForMonitor(hmonitor, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y)
+    };
+    match result {
+        Ok(()) if dpi_x > 0 => f64::from(dpi_x) / BASE_DPI,
+        _ => 1.0,
+    }
+}
+
+/// Set in `DISPLAYCONFIG_TARGET_DEVICE_NAME.flags` when the EDID manufacturer and product ids are filled in.
+const EDID_IDS_VALID: u32 = 0x4;
+
+/// Builds the monitor's id from what `DisplayConfigGetDeviceInfo` reports. Windows doesn't hand out
+/// the EDID serial here, so identical models are told apart by connector (e.g. `DP-2`), or by
+/// adapter and target id for connectors without a name.
+fn monitor_id(
+    device_name: &DISPLAYCONFIG_TARGET_DEVICE_NAME,
+    path: &DISPLAYCONFIG_PATH_INFO,
+) -> MonitorId {
+    let flags = unsafe { device_name.flags.Anonymous.value };
+    let hardware = if flags & EDID_IDS_VALID != 0 {
+        // The manufacturer id is stored with its bytes swapped relative to the EDID.
+        edid::hardware_id(
+            device_name.edidManufactureId.swap_bytes(),
+            device_name.edidProductCodeId,
+            None,
+        )
+    } else {
+        None
+    };
+    let kind = match device_name.outputTechnology {
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EXTERNAL
+        | DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_USB_TUNNEL => Some("DP"),
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DISPLAYPORT_EMBEDDED => Some("eDP"),
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HDMI => Some("HDMI"),
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_DVI => Some("DVI"),
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_HD15 => Some("VGA"),
+        DISPLAYCONFIG_OUTPUT_TECHNOLOGY_INTERNAL => Some("Internal"),
+        _ => None,
+    };
+    let connector = match kind {
+        Some(kind) => format!("{}-{}", kind, device_name.connectorInstance),
+        None => format!(
+            "{:X}{:08X}-{:X}",
+            path.targetInfo.adapterId.HighPart,
+            path.targetInfo.adapterId.LowPart,
+            path.targetInfo.id
+        ),
+    };
+    MonitorId {
+        hardware,
+        connector: Some(connector),
+    }
+}
+
+/// Enumerates monitors using the `QueryDisplayConfig` API.
+pub struct Win32Displays;
+
+impl Win32Displays {
+    pub fn new() -> Self {
+        enable_per_monitor_dpi_awareness();
+        Self
+    }
+}
+
+impl DisplayBackend for Win32Displays {
+    fn get_monitors(&self) -> Result<Vec<Monitor>> {
+        get_monitors()
+    }
+}
+
+fn get_monitors() -> Result<Vec<Monitor>> {
+    let mut num_paths: u32 = 0;
+    let mut num_modes: u32 = 0;
+
+    let status = unsafe {
+        GetDisplayConfigBufferSizes(QDC_ONLY_ACTIVE_PATHS, &mut num_paths, &mut num_modes)
+    };
+    if status != ERROR_SUCCESS {
+        eyre::bail!("GetDisplayConfigBufferSizes failed: {:?}", status);
+    }
+
+    // Allocate buffers for paths and modes.
+    let mut paths: Vec<DISPLAYCONFIG_PATH_INFO> = Vec::with_capacity(num_paths as usize);
+    let mut modes: Vec<DISPLAYCONFIG_MODE_INFO> = Vec::with_capacity(num_modes as usize);
+
+    let status = unsafe {
+        QueryDisplayConfig(
+            QDC_ONLY_ACTIVE_PATHS,
+            &mut num_paths,
+            paths.as_mut_ptr(),
+            &mut num_modes,
+            modes.as_mut_ptr(),
+            None,
+        )
+    };
+    if status != ERROR_SUCCESS {
+        eyre::bail!("QueryDisplayConfig failed: {:?}", status);
+    }
+
+    // Tell Rust the vectors have been fully populated.
+    unsafe {
+        paths.set_len(num_paths as usize);
+        modes.set_len(num_modes as usize);
+    }
+
+    let mut monitors = Vec::new();
+
+    for path in &paths {
+        // Use DISPLAYCONFIG_TARGET_DEVICE_NAME to retrieve a friendly monitor name.
+        let mut device_name: DISPLAYCONFIG_TARGET_DEVICE_NAME = unsafe { zeroed() };
+        device_name.header.size = size_of::<DISPLAYCONFIG_TARGET_DEVICE_NAME>() as u32;
+        device_name.header.adapterId = path.targetInfo.adapterId;
+        device_name.header.id = path.targetInfo.id;
+        device_name.header.r#type = DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME;
+
+        let res = unsafe { DisplayConfigGetDeviceInfo(&mut device_name.header) };
+        if res != ERROR_SUCCESS.0 as i32 {
+            continue;
+        }
+        let mut name = String::from_utf16_lossy(&device_name.monitorFriendlyDeviceName);
+        name = name.trim_end_matches('\0').to_string();
+
+        // Extract position and size from the source mode info.
+        let (x, y, width, height) = {
+            let mut x = 0;
+            let mut y = 0;
+            let mut width = 0;
+            let mut height = 0;
+            let mode_info_idx = unsafe { path.sourceInfo.Anonymous.modeInfoIdx };
+            if mode_info_idx != DISPLAYCONFIG_PATH_MODE_IDX_INVALID {
+                let mode_index = mode_info_idx as usize;
+                if mode_index < modes.len() {
+                    let mode = &modes[mode_index];
+                    if mode.infoType == DISPLAYCONFIG_MODE_INFO_TYPE_SOURCE {
+                        let source_mode = unsafe { mode.Anonymous.sourceMode };
+                        x = source_mode.position.x;
+                        y = source_mode.position.y;
+                        width = source_mode.width as i32;
+                        height = source_mode.height as i32;
+                    }
+                }
+            }
+            (x, y, width, height)
+        };
+
+        // Source modes are in device pixels regardless of the process's DPI awareness.
+        let physical = Rect::from_origin_size(x, y, width, height);
+        let scale = scale_for(physical);
+        monitors.push(Monitor {
+            name,
+            description: None,
+            id: monitor_id(&device_name, path),
+            physical,
+            logical: physical_to_logical(physical, scale),
+            scale,
+        });
+    }
+
+    Ok(monitors)
+}
@@ -0,0 +1,209 @@
+use crate::dpi::physical_to_logical;
+use crate::monitors::edid;
+use crate::monitors::DisplayBackend;
+use crate::monitors::Monitor;
+use crate::monitors::MonitorId;
+use crate::rect::Rect;
+use eyre::Result;
+use x11rb::connection::Connection;
+use x11rb::connection::RequestConnection;
+use x11rb::protocol::randr;
+use x11rb::protocol::randr::ConnectionExt as _;
+use x11rb::protocol::xproto::Atom;
+use x11rb::protocol::xproto::AtomEnum;
+use x11rb::protocol::xproto::ConnectionExt as _;
+use x11rb::protocol::xproto::Window;
+use x11rb::rust_connection::RustConnection;
+use x11rb::NONE;
+
+/// Enumerates monitors on an X11 display using XRandR.
+pub struct X11Displays {
+    conn: RustConnection,
+    root: Window,
+}
+
+impl X11Displays {
+    /// Connects to the display named by `$DISPLAY`.
+    pub fn connect() -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+        Ok(Self { conn, root })
+    }
+
+    /// Uses RandR 1.5 `GetMonitors`, which also reports monitors made with `xrandr --setmonitor`.
+    fn get_randr_monitors(&self, scale: f64) -> Result<Vec<Monitor>> {
+        let reply = self.conn.randr_get_monitors(self.root, true)?.reply()?;
+        let mut monitors = Vec::with_capacity(reply.monitors.len());
+        for info in reply.monitors {
+            // Prefer the product name of the first output; virtual monitors have no outputs.
+            let edid = match info.outputs.first() {
+                Some(&output) => self.output_edid(output)?,
+                None => Vec::new(),
+            };
+            let connector = self.atom_name(info.name)?;
+            let name = edid::product_name(&edid).unwrap_or_else(|| connector.clone());
+            let physical = Rect::from_origin_size(
+                info.x.into(),
+                info.y.into(),
+                info.width.into(),
+                info.height.into(),
+            );
+            monitors.push(Monitor {
+                name,
+                description: None,
+                id: MonitorId {
+                    hardware: edid::identity(&edid),
+                    connector: Some(connector),
+                },
+                physical,
+                logical: physical_to_logical(physical, scale),
+                scale,
+            });
+        }
+        Ok(monitors)
+    }
+
+    /// Pre-1.5 fallback: one monitor per connected output that is driving a CRTC.
+    fn get_crtc_monitors(&self, scale: f64) -> Result<Vec<Monitor>> {
+        let resources = self
+            .conn
+            .randr_get_screen_resources_current(self.root)?
+            .reply()?;
+        let mut monitors = Vec::new();
+        for &output in &resources.outputs {
+            let info = self
+                .conn
+                .randr_get_output_info(output, resources.config_timestamp)?
+                .reply()?;
+            if info.connection != randr::Connection::CONNECTED || info.crtc == NONE {
+                continue;
+            }
+            let crtc = self
+                .conn
+                .randr_get_crtc_info(info.crtc, resources.config_timestamp)?
+                .reply()?;
+            let edid = self.output_edid(output)?;
+            let connector = String::from_utf8_lossy(&info.name).into_owned();
+            let name = edid::product_name(&edid).unwrap_or_else(|| connector.clone());
+            let physical = Rect::from_origin_size(
+                crtc.x.into(),
+                crtc.y.into(),
+                crtc.width.into(),
+                crtc.height.into(),
+            );
+            monitors.push(Monitor {
+                name,
+                description: None,
+                id: MonitorId {
+                    hardware: edid::identity(&edid),
+                    connector: Some(connector),
+                },
+                physical,
+                logical: physical_to_logical(physical, scale),
+                scale,
+            });
+        }
+        Ok(monitors)
+    }
+
+    /// Reads the output's EDID property; empty when the driver doesn't provide one.
+    fn output_edid(&self, output: randr::Output) -> Result<Vec<u8>> {
+        let edid_atom = self.conn.intern_atom(true, b"EDID")?.reply()?.atom;
+        if edid_atom == NONE {
+            return Ok(Vec::new());
+        }
+        let property = self
+            .conn
+            .randr_get_output_property(output, edid_atom, AtomEnum::INTEGER, 0, 64, false, false)?
+            .reply()?;
+        Ok(property.data)
+    }
+
+    /// X11 has no per-monitor scaling; desktops scale every client through the `Xft.dpi` resource instead.
+    fn xft_scale(&self) -> Result<f64> {
+        let property = self
+            .conn
+            .get_property(
+                false,
+                self.root,
+                AtomEnum::RESOURCE_MANAGER,
+                AtomEnum::STRING,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+        let resources = String::from_utf8_lossy(&property.value);
+        let dpi = resources
+            .lines()
+            .find_map(|line| line.strip_prefix("Xft.dpi:"))
+            .and_then(|value| value.trim().parse::<f64>().ok());
+        Ok(dpi.map_or(1.0, |dpi| dpi / 96.0))
+    }
+
+    fn atom_name(&self, atom: Atom) -> Result<String> {
+        let reply = self.conn.get_atom_name(atom)?.reply()?;
+        Ok(String::from_utf8_lossy(&reply.name).into_owned())
+    }
+}
+
+impl DisplayBackend for X11Displays {
+    fn get_monitors(&self) -> Result<Vec<Monitor>> {
+        if self
+            .conn
+            .extension_information(randr::X11_EXTENSION_NAME)?
+            .is_none()
+        {
+            eyre::bail!("The X server does not support the RandR extension");
+        }
+        let scale = self.xft_scale()?;
+        let version = self.conn.randr_query_version(1, 5)?.reply()?;
+        if (version.major_version, version.minor_version) >= (1, 5) {
+            let monitors = self.get_randr_monitors(scale)?;
+            if !monitors.is_empty() {
+                return Ok(monitors);
+            }
+        }
+        self.get_crtc_monitors(scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn xrandr(args: &[&str]) {
+        let status = Command::new("xrandr").args(args).status().unwrap();
+        assert!(status.success(), "xrandr {:?} failed", args);
+    }
+
+    #[test]
+    #[ignore = "needs a 3840x1080 X server and xrandr, e.g. `xvfb-run -s '-screen 0 3840x1080x24' cargo test -- --ignored`"]
+    fn virtual_monitors_come_from_randr_1_5() {
+        // Monitors without outputs, i.e. no EDID, split the screen in two.
+        xrandr(&["--setmonitor", "CL-LEFT", "1920/508x1080/286+0+0", "none"]);
+        xrandr(&[
+            "--setmonitor",
+            "CL-RIGHT",
+            "1920/508x1080/286+1920+0",
+            "none",
+        ]);
+        let monitors = X11Displays::connect().unwrap().get_monitors();
+        xrandr(&["--delmonitor", "CL-LEFT"]);
+        xrandr(&["--delmonitor", "CL-RIGHT"]);
+
+        let monitors = monitors.unwrap();
+        let find = |name: &str| {
+            monitors
+                .iter()
+                .find(|monitor| monitor.name == name)
+                .unwrap_or_else(|| panic!("no {} in {:?}", name, monitors))
+        };
+        let left = find("CL-LEFT");
+        assert_eq!(left.physical, Rect::from_origin_size(0, 0, 1920, 1080));
+        assert_eq!(left.id.connector.as_deref(), Some("CL-LEFT"));
+        assert_eq!(left.id.hardware, None);
+        let right = find("CL-RIGHT");
+        assert_eq!(right.physical, Rect::from_origin_size(1920, 0, 1920, 1080));
+    }
+}
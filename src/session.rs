@@ -0,0 +1,15 @@
+/// The kind of graphical session we are running under on Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinuxSession {
+    Wayland,
+    X11,
+}
+
+/// Detects the session type from the environment, preferring Wayland when both are available.
+pub fn detect() -> LinuxSession {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        LinuxSession::Wayland
+    } else {
+        LinuxSession::X11
+    }
+}
@@ -1,31 +1,278 @@
-use crossterm::event::{read, Event, KeyCode, KeyEvent};
+use crate::key::Key;
+use crossterm::event::read;
+use crossterm::event::Event;
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEvent;
+use crossterm::event::KeyEventKind;
+use crossterm::event::KeyEventState;
+use crossterm::event::KeyModifiers;
+use crossterm::event::MediaKeyCode;
+use crossterm::terminal::disable_raw_mode;
+use crossterm::terminal::enable_raw_mode;
 use eyre::Result;
+use std::fmt;
 
-/// Waits for the user to press a key and returns its virtual-key code.
-pub fn pick_toggle_key() -> Result<u32> {
-    println!("Please press the key you would like to use as the toggle key... (except F12)");
+/// A set of modifier keys held down as part of a chord.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers(u8);
 
-    loop {
-        if let Event::Key(KeyEvent { code, .. }) = read()? {
-            let vk = match code {
-                // check if F12
-                KeyCode::F(12) => {
-                    println!("F12 is not allowed, see https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-registerhotkey#remarks");
-                    continue;
-                }
-                KeyCode::F(n) => {
-                    // Windows virtual key code for F1 is 0x70.
-                    0x70 + (n as u32) - 1
-                }
-                KeyCode::Char(c) => {
-                    // Convert to uppercase and use its ASCII code.
-                    c.to_ascii_uppercase() as u32
-                }
-                // Ignore other keys (e.g. Enter, Esc) and continue looping.
-                _ => continue,
+impl Modifiers {
+    pub const NONE: Modifiers = Modifiers(0);
+    pub const CTRL: Modifiers = Modifiers(1);
+    pub const ALT: Modifiers = Modifiers(2);
+    pub const SHIFT: Modifiers = Modifiers(4);
+    pub const SUPER: Modifiers = Modifiers(8);
+
+    /// In the order they are written in, e.g. `Ctrl+Alt+Shift+Super+L`.
+    const NAMES: [(Modifiers, &'static str); 4] = [
+        (Modifiers::CTRL, "Ctrl"),
+        (Modifiers::ALT, "Alt"),
+        (Modifiers::SHIFT, "Shift"),
+        (Modifiers::SUPER, "Super"),
+    ];
+
+    pub fn contains(self, other: Modifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    fn parse(name: &str) -> Option<Modifiers> {
+        match name.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => Some(Modifiers::CTRL),
+            "alt" | "option" => Some(Modifiers::ALT),
+            "shift" => Some(Modifiers::SHIFT),
+            "super" | "win" | "windows" | "meta" | "cmd" => Some(Modifiers::SUPER),
+            _ => None,
+        }
+    }
+}
+
+impl std::ops::BitOr for Modifiers {
+    type Output = Modifiers;
+
+    fn bitor(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitOrAssign for Modifiers {
+    fn bitor_assign(&mut self, other: Modifiers) {
+        self.0 |= other.0;
+    }
+}
+
+/// A key plus the modifiers that must be held with it, e.g. `Ctrl+Alt+L`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub modifiers: Modifiers,
+    pub key: Key,
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in Modifiers::NAMES {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{}+", name)?;
+            }
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// Reads a single key press from the terminal.
+/// Raw mode is needed so that terminals which buffer input by line (e.g. on Linux) report keys immediately.
+fn read_key_event() -> Result<KeyEvent> {
+    enable_raw_mode()?;
+    let result = loop {
+        match read() {
+            Ok(Event::Key(event)) if event.kind == KeyEventKind::Press => break Ok(event),
+            Ok(_) => continue,
+            Err(e) => break Err(e.into()),
+        }
+    };
+    disable_raw_mode()?;
+    result
+}
+
+/// Maps a terminal key event to the physical key behind it, where the terminal tells us enough.
+fn key_from_event(event: &KeyEvent) -> Option<Key> {
+    // Terminals only tell numpad keys apart when they support the keyboard enhancement protocol.
+    if event.state.contains(KeyEventState::KEYPAD) {
+        if let KeyCode::Char(c) = event.code {
+            return match c {
+                '0'..='9' => Key::from_vk(0x60 + c.to_digit(10)?),
+                '*' => Some(Key::NumpadMultiply),
+                '+' => Some(Key::NumpadAdd),
+                '-' => Some(Key::NumpadSubtract),
+                '.' => Some(Key::NumpadDecimal),
+                '/' => Some(Key::NumpadDivide),
+                _ => None,
             };
-            println!("Toggle key set to virtual key code: {:#X}", vk);
-            return Ok(vk);
+        }
+    }
+    Some(match event.code {
+        KeyCode::Char(c) => return Key::from_char(c),
+        KeyCode::F(n) => return Key::function(n),
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Left => Key::Left,
+        KeyCode::Right => Key::Right,
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Tab | KeyCode::BackTab => Key::Tab,
+        KeyCode::Delete => Key::Delete,
+        KeyCode::Insert => Key::Insert,
+        KeyCode::Esc => Key::Escape,
+        KeyCode::CapsLock => Key::CapsLock,
+        KeyCode::ScrollLock => Key::ScrollLock,
+        KeyCode::NumLock => Key::NumLock,
+        KeyCode::PrintScreen => Key::PrintScreen,
+        KeyCode::Pause => Key::Pause,
+        KeyCode::Menu => Key::Menu,
+        KeyCode::Media(MediaKeyCode::PlayPause) => Key::MediaPlayPause,
+        KeyCode::Media(MediaKeyCode::Stop) => Key::MediaStop,
+        KeyCode::Media(MediaKeyCode::TrackNext) => Key::MediaNext,
+        KeyCode::Media(MediaKeyCode::TrackPrevious) => Key::MediaPrevious,
+        KeyCode::Media(MediaKeyCode::LowerVolume) => Key::VolumeDown,
+        KeyCode::Media(MediaKeyCode::RaiseVolume) => Key::VolumeUp,
+        KeyCode::Media(MediaKeyCode::MuteVolume) => Key::VolumeMute,
+        _ => return None,
+    })
+}
+
+/// Parses a chord given on the command line or in the config: `+`-separated modifiers
+/// (`ctrl`, `alt`, `shift`, `super`) followed by a key, e.g. `ctrl+alt+L` or just `F9`.
+pub fn parse_toggle_key(chord: &str) -> Result<Chord> {
+    let mut parts: Vec<&str> = chord.split('+').map(str::trim).collect();
+    // `ctrl++` would otherwise complain about an empty modifier.
+    if parts.len() > 1 && parts.contains(&"") {
+        eyre::bail!(
+            "{:?} has an empty modifier or key; `+` only separates them, so write the + key as `plus`",
+            chord.trim()
+        );
+    }
+    let key = parts.pop().unwrap_or_default();
+    let mut modifiers = Modifiers::NONE;
+    for part in parts {
+        match Modifiers::parse(part) {
+            Some(modifier) => modifiers |= modifier,
+            None => eyre::bail!(
+                "Unrecognised modifier {:?} in {:?}; expected ctrl, alt, shift or super",
+                part,
+                chord.trim()
+            ),
+        }
+    }
+    Ok(Chord {
+        modifiers,
+        key: parse_key(key)?,
+    })
+}
+
+/// Parses a single key, see [`Key`]'s `FromStr`, rejecting the ones Windows won't register.
+fn parse_key(name: &str) -> Result<Key> {
+    let key: Key = name.parse()?;
+    if cfg!(windows) && key == Key::F12 {
+        eyre::bail!("F12 is reserved for the debugger and can't be used as a hotkey");
+    }
+    Ok(key)
+}
+
+/// Waits for the user to press a key, optionally with modifiers held, and returns the chord.
+pub fn pick_toggle_key() -> Result<Chord> {
+    // Only Windows keeps F12 for the debugger; other platforms can grab it like any other key.
+    let except = if cfg!(windows) { " (except F12)" } else { "" };
+    println!("Please press the key you would like to use as the toggle key, optionally with Ctrl/Alt/Shift...{}", except);
+
+    loop {
+        let event = read_key_event()?;
+        let held = event.modifiers;
+        let key = match key_from_event(&event) {
+            Some(Key::F12) if cfg!(windows) => {
+                println!("F12 is not allowed, see https://learn.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-registerhotkey#remarks");
+                continue;
+            }
+            // A stray Enter or Esc at the prompt is more likely a habit than a choice.
+            Some(Key::Enter | Key::Escape) if held.is_empty() => continue,
+            Some(key) => key,
+            None => {
+                println!("The terminal doesn't say which key that was; please press another, or pass it with --key.");
+                continue;
+            }
+        };
+        let mut modifiers = Modifiers::NONE;
+        for (flag, modifier) in [
+            (KeyModifiers::CONTROL, Modifiers::CTRL),
+            (KeyModifiers::ALT, Modifiers::ALT),
+            (KeyModifiers::SHIFT, Modifiers::SHIFT),
+            (KeyModifiers::SUPER, Modifiers::SUPER),
+        ] {
+            if held.contains(flag) {
+                modifiers |= modifier;
+            }
+        }
+        // Terminals report Shift for capital letters, which isn't a chord the user meant.
+        if matches!(event.code, KeyCode::Char(c) if c.is_ascii_uppercase())
+            && modifiers == Modifiers::SHIFT
+        {
+            modifiers = Modifiers::NONE;
+        }
+        let chord = Chord { modifiers, key };
+        println!("Toggle key set to {}", chord);
+        return Ok(chord);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(chord: &str) -> String {
+        parse_toggle_key(chord).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn chords_parse_with_any_modifier_spelling() {
+        let chord = parse_toggle_key("ctrl+alt+L").unwrap();
+        assert_eq!(chord.modifiers, Modifiers::CTRL | Modifiers::ALT);
+        assert_eq!(chord.key, Key::L);
+        assert_eq!(parse_toggle_key(" Control + ALT + l ").unwrap(), chord);
+        for spelling in ["win+L", "CMD+L", "super+L"] {
+            assert_eq!(
+                parse_toggle_key(spelling).unwrap().modifiers,
+                Modifiers::SUPER
+            );
+        }
+        assert_eq!(parse_toggle_key("F9").unwrap().modifiers, Modifiers::NONE);
+        assert_eq!(parse_toggle_key("ctrl+plus").unwrap().key, Key::NumpadAdd);
+    }
+
+    #[test]
+    fn chords_display_as_they_parse() {
+        for text in ["Ctrl+Alt+L", "Shift+Super+F9", "PageUp"] {
+            let chord = parse_toggle_key(text).unwrap();
+            assert_eq!(parse_toggle_key(&chord.to_string()).unwrap(), chord);
+        }
+        assert_eq!(
+            parse_toggle_key("shift+ctrl+l").unwrap().to_string(),
+            "Ctrl+Shift+L"
+        );
+    }
+
+    #[test]
+    fn bad_chords_say_what_is_wrong() {
+        assert!(error("hyper+L").contains("Unrecognised modifier \"hyper\""));
+        if cfg!(windows) {
+            assert!(error("F12").contains("reserved"));
+            assert!(error("ctrl+F12").contains("reserved"));
+        } else {
+            assert_eq!(parse_toggle_key("ctrl+F12").unwrap().key, Key::F12);
+        }
+        for chord in ["ctrl++", "ctrl+", "+L"] {
+            assert!(error(chord).contains("`plus`"), "{}", error(chord));
         }
     }
 }
@@ -0,0 +1,140 @@
+use rodio::source::SineWave;
+use rodio::source::Zero;
+use rodio::Source;
+use std::time::Duration;
+
+/// The sample rate every tone is rendered at.
+pub const SAMPLE_RATE: u32 = 48_000;
+
+// Monitor-number beeps: short enough that counting to four doesn't drag on.
+const BEEP_HZ: f32 = 880.0;
+const BEEP_LENGTH: Duration = Duration::from_millis(90);
+
+// The leader cue: one higher blip, so it isn't mistaken for monitor 1.
+const ARMED_HZ: f32 = 1320.0;
+const ARMED_LENGTH: Duration = Duration::from_millis(60);
+
+// Lock and release steps start here and move two semitones per beep, so nine monitors still fit
+// in about an octave and a half.
+const STEP_BASE_HZ: f32 = 660.0;
+const STEP_SEMITONES: f32 = 2.0;
+
+type Tone = Box<dyn Source<Item = f32> + Send>;
+
+fn tone(hz: f32, length: Duration) -> impl Source<Item = f32> {
+    SineWave::new(hz).take_duration(length).amplify(0.2)
+}
+
+fn silence(length: Duration) -> impl Source<Item = f32> {
+    Zero::<f32>::new(1, SAMPLE_RATE).take_duration(length)
+}
+
+/// A beep at each pitch with a gap after it.
+fn sequence(pitches: Vec<f32>) -> impl Source<Item = f32> {
+    rodio::source::from_iter(pitches.into_iter().flat_map(|hz| -> [Tone; 2] {
+        [
+            Box::new(tone(hz, BEEP_LENGTH)),
+            Box::new(silence(BEEP_LENGTH)),
+        ]
+    }))
+}
+
+/// The pitch of the `step`th beep of a lock or release, counting from 0.
+fn step_pitch(step: usize) -> f32 {
+    STEP_BASE_HZ * 2f32.powf(step as f32 * STEP_SEMITONES / 12.0)
+}
+
+/// `count` short beeps at one pitch, so the monitor number can be told by ear.
+pub fn beeps(count: usize) -> impl Source<Item = f32> {
+    sequence(vec![BEEP_HZ; count])
+}
+
+/// One beep per monitor number, each a step higher than the last.
+pub fn locked(monitor: usize) -> impl Source<Item = f32> {
+    sequence((0..monitor).map(step_pitch).collect())
+}
+
+/// The beeps of [`locked`] going back down, for releasing the lock on that monitor.
+pub fn released(monitor: usize) -> impl Source<Item = f32> {
+    sequence((0..monitor).rev().map(step_pitch).collect())
+}
+
+/// The cue for an armed leader key.
+pub fn armed() -> impl Source<Item = f32> {
+    tone(ARMED_HZ, ARMED_LENGTH)
+}
+
+/// The pitch of each beep in `samples`, estimated from how often the wave crosses zero.
+#[cfg(test)]
+pub fn beep_pitches(samples: &[f32]) -> Vec<f32> {
+    let gap = (SAMPLE_RATE as usize) / 100;
+    let mut pitches = Vec::new();
+    let mut start = None;
+    let mut quiet = 0;
+    for (i, &sample) in samples.iter().chain([0.0; 1000].iter()).enumerate() {
+        if sample != 0.0 {
+            start.get_or_insert(i);
+            quiet = 0;
+            continue;
+        }
+        quiet += 1;
+        // A beep ends once it has been silent for longer than a zero crossing lasts.
+        if let (Some(from), true) = (start, quiet > gap) {
+            let beep = &samples[from..i + 1 - quiet];
+            let crossings = beep
+                .windows(2)
+                .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+                .count();
+            let seconds = beep.len() as f32 / SAMPLE_RATE as f32;
+            pitches.push(crossings as f32 / 2.0 / seconds);
+            start = None;
+        }
+    }
+    pitches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: impl Source<Item = f32>) -> Vec<f32> {
+        assert_eq!(source.sample_rate(), SAMPLE_RATE);
+        assert_eq!(source.channels(), 1);
+        source.collect()
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} vs {:?}",
+            actual,
+            expected
+        );
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < expected * 0.03,
+                "{:?} vs {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn beeps_count_the_monitor_at_one_pitch() {
+        let samples = render(beeps(3));
+        // Each beep and the gap after it are 90 ms.
+        assert_eq!(samples.len(), 3 * 2 * SAMPLE_RATE as usize * 90 / 1000);
+        assert_close(&beep_pitches(&samples), &[BEEP_HZ; 3]);
+    }
+
+    #[test]
+    fn locking_rises_and_releasing_falls_once_per_monitor_number() {
+        let rising = [660.0, 740.8, 831.5];
+        assert_close(&beep_pitches(&render(locked(3))), &rising);
+        let falling: Vec<f32> = rising.iter().rev().copied().collect();
+        assert_close(&beep_pitches(&render(released(3))), &falling);
+        assert_close(&beep_pitches(&render(locked(1))), &rising[..1]);
+    }
+}